@@ -0,0 +1,435 @@
+use std::{collections::BTreeSet, time::Instant};
+
+use crate::{color::{lerp_color, Color}, random::Random, render::{Frame, FrameCell, TerminalSize}, state::{Easing, Outro, OutroStyle, ProgramMode, ProgramState, RevealOrder}};
+
+const DECODE_GLYPH_INTERVAL: u64 = 60;
+const DEFAULT_OUTRO_DURATION: u64 = 1000;
+
+pub struct Animation {
+   mode: ProgramMode,
+   duration: Option<u64>,
+   reverse: bool,
+   started: Instant,
+   finished: bool
+}
+
+impl Animation {
+   pub fn start(state: &ProgramState) -> Animation {
+      Animation { mode: state.mode, duration: None, reverse: false, started: Instant::now(), finished: false }
+   }
+
+   // Outros play a mode backwards, taking the message away instead of revealing it. The
+   // reverse outro replays the render mode with its own timing unless a duration was given.
+   pub fn outro(outro: &Outro, state: &ProgramState) -> Animation {
+      let duration = outro.duration.or(state.duration).unwrap_or(DEFAULT_OUTRO_DURATION);
+
+      let (mode, duration) = match outro.style {
+         OutroStyle::Reverse => (state.mode, outro.duration),
+         OutroStyle::Fade => (ProgramMode::Fade, Some(duration)),
+         OutroStyle::Dissolve => (ProgramMode::Dissolve, Some(duration)),
+         OutroStyle::Wipe => (ProgramMode::Wipe, Some(duration))
+      };
+
+      Animation { mode, duration, reverse: true, started: Instant::now(), finished: false }
+   }
+
+   pub fn restart(&mut self) {
+      self.started = Instant::now();
+      self.finished = false;
+   }
+
+   pub fn skip(&mut self) {
+      self.finished = true;
+   }
+
+   pub fn is_running(&self) -> bool {
+      !self.finished
+   }
+
+   // Hides the parts of the frame that should not be visible yet, based on the time elapsed
+   // since the animation started. Once everything is revealed the animation is over and the
+   // frame is left untouched from then on.
+   pub fn apply(&mut self, frame: &mut Frame, state: &ProgramState, dimensions: &TerminalSize) {
+      if self.finished {
+         return;
+      }
+
+      let elapsed = self.started.elapsed().as_millis() as u64;
+
+      let revealed = match self.mode {
+         ProgramMode::Flash => true,
+         ProgramMode::CharByChar | ProgramMode::LineByLine | ProgramMode::WordByWord | ProgramMode::ColumnByColumn | ProgramMode::Dissolve => {
+            self.reveal_units(frame, state, elapsed)
+         },
+         ProgramMode::Fade | ProgramMode::Decode | ProgramMode::Wipe | ProgramMode::Slide => {
+            let progress = progress(elapsed, self.duration(state).unwrap_or(0));
+            let eased = ease(progress, state.easing);
+            let eased = if self.reverse { 1.0 - eased } else { eased };
+
+            match self.mode {
+               ProgramMode::Fade => fade_colors(frame, eased),
+               ProgramMode::Decode => decode_cells(frame, state, eased, elapsed),
+               ProgramMode::Wipe => wipe_cells(frame, eased, state.angle),
+               _ => slide_cells(frame, eased, state.angle, dimensions)
+            }
+
+            progress >= 1.0
+         }
+      };
+
+      // Outros leave nothing behind, whatever the mode shows at the end of its reversed run.
+      if revealed && self.reverse {
+         frame.rows.iter_mut().for_each(| row | row.fill(None));
+      }
+
+      self.finished = revealed;
+   }
+
+   // Modes that reveal the message piece by piece take a delay per piece, unless a total
+   // duration was given. The remaining modes always run for a fixed duration.
+   fn duration(&self, state: &ProgramState) -> Option<u64> {
+      if self.duration.is_some() {
+         return self.duration;
+      }
+
+      match self.mode {
+         ProgramMode::Flash => None,
+         ProgramMode::CharByChar | ProgramMode::LineByLine | ProgramMode::WordByWord | ProgramMode::ColumnByColumn => state.duration,
+         _ => Some(state.duration.unwrap_or(state.animation_delay))
+      }
+   }
+
+   fn reveal_units(&self, frame: &mut Frame, state: &ProgramState, elapsed: u64) -> bool {
+      let (units, total) = match self.mode {
+         ProgramMode::CharByChar => character_units(frame),
+         ProgramMode::LineByLine => line_units(frame),
+         ProgramMode::WordByWord => word_units(frame),
+         ProgramMode::ColumnByColumn => column_units(frame),
+         _ => dissolve_units(frame, state.seed)
+      };
+
+      let (shown, revealed) = match self.duration(state) {
+         Some(duration) => {
+            let progress = progress(elapsed, duration);
+
+            ((total as f32 * ease(progress, state.easing)).ceil() as usize, progress >= 1.0)
+         },
+         None => {
+            let shown = revealed_units(elapsed, state.animation_delay);
+
+            (shown, shown >= total)
+         }
+      };
+
+      let visible = if self.reverse { total.saturating_sub(shown) } else { shown };
+
+      for (y, x, unit) in units {
+         if unit >= visible {
+            frame.rows[y][x] = None;
+         }
+      }
+
+      revealed
+   }
+}
+
+fn progress(elapsed: u64, duration: u64) -> f32 {
+   if duration == 0 {
+      return 1.0;
+   }
+
+   (elapsed as f32 / duration as f32).min(1.0)
+}
+
+pub fn ease(progress: f32, easing: Easing) -> f32 {
+   match easing {
+      Easing::Linear => progress,
+      Easing::EaseIn => progress.powi(3),
+      Easing::EaseOut => 1.0 - (1.0 - progress).powi(3),
+      Easing::EaseInOut if progress < 0.5 => 4.0 * progress.powi(3),
+      Easing::EaseInOut => 1.0 - (2.0 - 2.0 * progress).powi(3) / 2.0
+   }
+}
+
+fn revealed_units(elapsed: u64, delay: u64) -> usize {
+   if delay == 0 {
+      return usize::MAX;
+   }
+
+   (elapsed / delay + 1) as usize
+}
+
+fn visible_cells(frame: &Frame) -> Vec<(usize, usize)> {
+   let mut positions = Vec::new();
+
+   for (y, row) in frame.rows.iter().enumerate() {
+      for (x, cell) in row.iter().enumerate() {
+         if cell.as_ref().is_some_and(| cell | !cell.is_blank()) {
+            positions.push((y, x));
+         }
+      }
+   }
+
+   positions
+}
+
+// Cells keep cycling through random glyphs and settle on their real grapheme one after the
+// other, so the last one settles exactly when the animation is over.
+fn decode_cells(frame: &mut Frame, state: &ProgramState, progress: f32, elapsed: u64) {
+   let mut positions = visible_cells(frame);
+   let total = positions.len() as f32;
+   let step = elapsed / DECODE_GLYPH_INTERVAL;
+
+   if state.reveal_order == RevealOrder::Random {
+      Random::new(state.seed).shuffle(&mut positions);
+   }
+
+   for (rank, (y, x)) in positions.into_iter().enumerate() {
+      if progress < (rank + 1) as f32 / total {
+         let mut random = Random::new(state.seed ^ ((y as u64) << 48) ^ ((x as u64) << 32) ^ step);
+         let glyph = &state.decode_characters[random.next_below(state.decode_characters.len())];
+
+         if let Some(cell) = frame.rows[y][x].as_mut() {
+            cell.value = glyph.clone();
+         }
+      }
+   }
+}
+
+// The functions below split the visible cells of a frame into the pieces revealed one after
+// the other, returning each cell with the index of its piece and the number of pieces.
+
+fn character_units(frame: &Frame) -> (Vec<(usize, usize, usize)>, usize) {
+   let units: Vec<(usize, usize, usize)> = visible_cells(frame).into_iter().enumerate().map(| (unit, (y, x)) | (y, x, unit)).collect();
+   let total = units.len();
+
+   (units, total)
+}
+
+fn line_units(frame: &Frame) -> (Vec<(usize, usize, usize)>, usize) {
+   let mut units = Vec::new();
+   let mut total = 0;
+   let mut last_line = None;
+
+   for (y, x) in visible_cells(frame) {
+      if last_line != Some(y) {
+         last_line = Some(y);
+         total += 1;
+      }
+
+      units.push((y, x, total - 1));
+   }
+
+   (units, total)
+}
+
+// A word is any run of adjacent visible cells on the same row, so punctuation stays with its word.
+fn word_units(frame: &Frame) -> (Vec<(usize, usize, usize)>, usize) {
+   let mut units = Vec::new();
+   let mut total = 0;
+   let mut word_end = None;
+
+   for (y, x) in visible_cells(frame) {
+      if word_end != Some((y, x)) {
+         total += 1;
+      }
+
+      word_end = frame.rows[y][x].as_ref().map(| cell | (y, x + cell.width()));
+      units.push((y, x, total - 1));
+   }
+
+   (units, total)
+}
+
+// Big-font messages are revealed one whole glyph at a time, leaving borders and other cells
+// that are not part of a glyph in place. Any other message is revealed one column at a time.
+fn column_units(frame: &Frame) -> (Vec<(usize, usize, usize)>, usize) {
+   let positions = visible_cells(frame);
+   let glyph_of = | y: usize, x: usize | frame.rows[y][x].as_ref().and_then(| cell | cell.glyph);
+   let glyphs: BTreeSet<usize> = positions.iter().filter_map(| (y, x) | glyph_of(*y, *x)).collect();
+
+   if !glyphs.is_empty() {
+      let ranks: Vec<usize> = glyphs.iter().copied().collect();
+
+      let units = positions.into_iter().filter_map(| (y, x) | {
+         glyph_of(y, x).map(| glyph | (y, x, ranks.binary_search(&glyph).unwrap_or(0)))
+      }).collect();
+
+      return (units, ranks.len());
+   }
+
+   let columns: Vec<usize> = positions.iter().map(| (_, x) | *x).collect::<BTreeSet<usize>>().into_iter().collect();
+   let units = positions.into_iter().map(| (y, x) | (y, x, columns.binary_search(&x).unwrap_or(0))).collect();
+
+   (units, columns.len())
+}
+
+// A random order that only depends on the seed, so the same seed always dissolves the same way.
+fn dissolve_units(frame: &Frame, seed: u64) -> (Vec<(usize, usize, usize)>, usize) {
+   let mut positions = visible_cells(frame);
+
+   Random::new(seed).shuffle(&mut positions);
+
+   let total = positions.len();
+   let units = positions.into_iter().enumerate().map(| (unit, (y, x)) | (y, x, unit)).collect();
+
+   (units, total)
+}
+
+// Blends every cell from black towards its final colors. Foregrounds start from the cell
+// background when there is one, so colored blocks fade in as a whole.
+fn fade_colors(frame: &mut Frame, progress: f32) {
+   let black = Color { r: 0.0, g: 0.0, b: 0.0 };
+
+   for cell in frame.rows.iter_mut().flatten().flatten() {
+      let background = cell.background.map(| background | lerp_color(black, background, progress));
+
+      cell.foreground = lerp_color(background.unwrap_or(black), cell.foreground, progress);
+      cell.background = background;
+   }
+}
+
+// Reveals the cells in the direction of the gradient angle, like a line sweeping across the
+// message, so an angle of 0 wipes from left to right and 90 from top to bottom.
+fn wipe_cells(frame: &mut Frame, progress: f32, angle: f32) {
+   let angle = angle.to_radians();
+   let (direction_x, direction_y) = (angle.cos(), angle.sin());
+   let positions = visible_cells(frame);
+
+   let projections: Vec<f32> = positions.iter().map(| (y, x) | *x as f32 * direction_x + *y as f32 * direction_y).collect();
+   let start = projections.iter().copied().fold(f32::MAX, f32::min);
+   let end = projections.iter().copied().fold(f32::MIN, f32::max);
+   let limit = start + (end - start) * progress;
+
+   for ((y, x), projection) in positions.into_iter().zip(projections) {
+      let hidden = progress <= 0.0 || (progress < 1.0 && projection > limit);
+
+      if hidden {
+         frame.rows[y][x] = None;
+      }
+   }
+}
+
+// Moves the whole message in from the terminal edge opposite to the gradient angle, along the
+// axis closest to it, until it reaches its place in the frame. Frames shorter than the terminal
+// are extended, so the message can come from below them.
+fn slide_cells(frame: &mut Frame, progress: f32, angle: f32, dimensions: &TerminalSize) {
+   let positions = visible_cells(frame);
+
+   if positions.is_empty() {
+      return;
+   }
+
+   let terminal_width = frame.width.max(dimensions.width as usize);
+   let terminal_height = frame.rows.len().max(dimensions.height as usize);
+
+   frame.rows.resize(terminal_height, vec![None; frame.width]);
+   frame.height = terminal_height;
+
+   let (first_x, last_x) = positions.iter().fold((usize::MAX, 0), | (first, last), (_, x) | (first.min(*x), last.max(*x + 1)));
+   let (first_y, last_y) = positions.iter().fold((usize::MAX, 0), | (first, last), (y, _) | (first.min(*y), last.max(*y + 1)));
+
+   let angle = angle.to_radians();
+   let (direction_x, direction_y) = (angle.cos(), angle.sin());
+   let remaining = 1.0 - progress;
+
+   let (offset_x, offset_y) = if direction_x.abs() >= direction_y.abs() {
+      if direction_x >= 0.0 {
+         (-((last_x as f32 * remaining).round() as isize), 0)
+      } else {
+         (((terminal_width - first_x) as f32 * remaining).round() as isize, 0)
+      }
+   } else if direction_y >= 0.0 {
+      (0, -((last_y as f32 * remaining).round() as isize))
+   } else {
+      (0, ((terminal_height - first_y) as f32 * remaining).round() as isize)
+   };
+
+   shift_frame(frame, offset_x, offset_y);
+}
+
+fn shift_frame(frame: &mut Frame, offset_x: isize, offset_y: isize) {
+   if offset_x == 0 && offset_y == 0 {
+      return;
+   }
+
+   let mut rows: Vec<Vec<Option<FrameCell>>> = vec![vec![None; frame.width]; frame.rows.len()];
+
+   for (y, row) in frame.rows.iter_mut().enumerate() {
+      for (x, cell) in row.iter_mut().enumerate() {
+         let target_x = x as isize + offset_x;
+         let target_y = y as isize + offset_y;
+
+         if let Some(cell) = cell.take() {
+            let fits = target_x >= 0 && target_y >= 0 && target_x as usize + cell.width() <= frame.width && (target_y as usize) < rows.len();
+
+            if fits {
+               rows[target_y as usize][target_x as usize] = Some(cell);
+            }
+         }
+      }
+   }
+
+   frame.rows = rows;
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{parser::parse_message, render::render_frame, state::Border, typewriter::generate_message};
+
+   fn frame(text: &str, state: &ProgramState) -> Frame {
+      render_frame(&parse_message(text.to_string(), state), state, 20, 10)
+   }
+
+   fn unit_at(units: &[(usize, usize, usize)], y: usize, x: usize) -> Option<usize> {
+      units.iter().find(| (unit_y, unit_x, _) | (*unit_y, *unit_x) == (y, x)).map(| (_, _, unit) | *unit)
+   }
+
+   #[test]
+   fn splits_words_on_blank_cells() {
+      let (units, total) = word_units(&frame("ab, cd\nef", &ProgramState::builder().build()));
+
+      assert_eq!(total, 3);
+      assert_eq!([unit_at(&units, 0, 0), unit_at(&units, 0, 2)], [Some(0), Some(0)]);
+      assert_eq!([unit_at(&units, 0, 4), unit_at(&units, 0, 5)], [Some(1), Some(1)]);
+      assert_eq!(unit_at(&units, 1, 0), Some(2));
+   }
+
+   #[test]
+   fn keeps_wide_glyphs_in_one_word() {
+      let (_, total) = word_units(&frame("漢字 a", &ProgramState::builder().build()));
+
+      assert_eq!(total, 2);
+   }
+
+   #[test]
+   fn reveals_big_font_text_one_glyph_at_a_time() {
+      let state = ProgramState::builder().build();
+      let message = generate_message("HI".to_string());
+      let frame = render_frame(&message, &state, 40, 10);
+      let (units, total) = column_units(&frame);
+
+      assert_eq!(total, 2);
+      assert!(units.iter().all(| (y, x, unit) | frame.rows[*y][*x].as_ref().and_then(| cell | cell.glyph) == Some(*unit)));
+   }
+
+   #[test]
+   fn leaves_borders_out_of_the_glyph_columns() {
+      let characters = "┌─┐││└─┘".chars().map(| c | c.to_string()).collect();
+      let state = ProgramState::builder().border(Border { characters, vertical_padding: 0, horizontal_padding: 1, title: None, colors: None }).build();
+      let frame = render_frame(&generate_message("HI".to_string()), &state, 40, 10);
+      let (units, _) = column_units(&frame);
+
+      assert!(units.iter().all(| (y, x, _) | frame.rows[*y][*x].as_ref().is_some_and(| cell | cell.glyph.is_some())));
+      assert_eq!(unit_at(&units, 0, 0), None);
+   }
+
+   #[test]
+   fn reveals_plain_text_one_column_at_a_time() {
+      let (units, total) = column_units(&frame("ab\nc", &ProgramState::builder().build()));
+
+      assert_eq!(total, 2);
+      assert_eq!([unit_at(&units, 0, 0), unit_at(&units, 1, 0), unit_at(&units, 0, 1)], [Some(0), Some(0), Some(1)]);
+   }
+}
@@ -0,0 +1,248 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{color::Color, style::{Attribute, GraphemeStyle}};
+
+const STANDARD_PALETTE: [(u8, u8, u8); 16] = [
+   (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+   (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+   (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+   (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+];
+
+pub struct StyledSegment {
+   pub text: String,
+   pub style: Option<GraphemeStyle>
+}
+
+pub fn split_escape_sequences(line: &str, current_style: &mut GraphemeStyle) -> Vec<StyledSegment> {
+   let mut segments: Vec<StyledSegment> = Vec::new();
+   let mut text = String::new();
+   let mut chars = line.chars().peekable();
+
+   while let Some(c) = chars.next() {
+      if c != '\x1b' {
+         text.push(c);
+         continue;
+      }
+
+      match chars.next() {
+         Some('[') => {
+            let (parameters, final_byte) = read_control_sequence(&mut chars);
+
+            if final_byte == Some('m') {
+               push_segment(&mut segments, &mut text, current_style);
+               apply_sgr_parameters(&parameters, current_style);
+            }
+         },
+         Some(']') => {
+            skip_operating_system_command(&mut chars);
+         },
+         Some(intermediate) if ('\x20'..='\x2f').contains(&intermediate) => {
+            skip_escape_sequence(&mut chars);
+         },
+         _ => {}
+      }
+   }
+
+   push_segment(&mut segments, &mut text, current_style);
+
+   segments
+}
+
+fn push_segment(segments: &mut Vec<StyledSegment>, text: &mut String, style: &GraphemeStyle) {
+   if text.is_empty() {
+      return;
+   }
+
+   segments.push(StyledSegment {
+      text: std::mem::take(text),
+      style: if style.is_empty() { None } else { Some(*style) }
+   });
+}
+
+fn read_control_sequence(chars: &mut Peekable<Chars>) -> (String, Option<char>) {
+   let mut parameters = String::new();
+
+   for c in chars.by_ref() {
+      if ('\x40'..='\x7e').contains(&c) {
+         return (parameters, Some(c));
+      }
+
+      parameters.push(c);
+   }
+
+   (parameters, None)
+}
+
+fn skip_operating_system_command(chars: &mut Peekable<Chars>) {
+   while let Some(c) = chars.next() {
+      if c == '\x07' {
+         return;
+      }
+
+      if c == '\x1b' && chars.peek() == Some(&'\\') {
+         chars.next();
+         return;
+      }
+   }
+}
+
+// Escape sequences with intermediate bytes, like the charset designators "ESC ( B", end at the
+// first final byte; the two-byte "ESC X" forms are already consumed with their second byte.
+fn skip_escape_sequence(chars: &mut Peekable<Chars>) {
+   for c in chars.by_ref() {
+      if !('\x20'..='\x2f').contains(&c) {
+         return;
+      }
+   }
+}
+
+fn apply_sgr_parameters(parameters: &str, style: &mut GraphemeStyle) {
+   let codes: Vec<u16> = parameters.split(';')
+                                   .map(| parameter | parameter.split(':').next().unwrap_or("").parse::<u16>().unwrap_or(0))
+                                   .collect();
+   let mut index = 0;
+
+   while index < codes.len() {
+      let code = codes[index];
+
+      match code {
+         0 => *style = GraphemeStyle::default(),
+         1 => style.attributes.set(Attribute::Bold, true),
+         2 => style.attributes.set(Attribute::Dim, true),
+         3 => style.attributes.set(Attribute::Italic, true),
+         4 => style.attributes.set(Attribute::Underline, true),
+         5 | 6 => style.attributes.set(Attribute::Blink, true),
+         7 => style.attributes.set(Attribute::Reverse, true),
+         9 => style.attributes.set(Attribute::Strikethrough, true),
+         21 | 22 => {
+            style.attributes.set(Attribute::Bold, false);
+            style.attributes.set(Attribute::Dim, false);
+         },
+         23 => style.attributes.set(Attribute::Italic, false),
+         24 => style.attributes.set(Attribute::Underline, false),
+         25 => style.attributes.set(Attribute::Blink, false),
+         27 => style.attributes.set(Attribute::Reverse, false),
+         29 => style.attributes.set(Attribute::Strikethrough, false),
+         30..=37 => style.foreground = Some(palette_color(code - 30)),
+         38 => style.foreground = read_extended_color(&codes, &mut index),
+         39 => style.foreground = None,
+         40..=47 => style.background = Some(palette_color(code - 40)),
+         48 => style.background = read_extended_color(&codes, &mut index),
+         49 => style.background = None,
+         90..=97 => style.foreground = Some(palette_color(code - 90 + 8)),
+         100..=107 => style.background = Some(palette_color(code - 100 + 8)),
+         _ => {}
+      }
+
+      index += 1;
+   }
+}
+
+fn read_extended_color(codes: &[u16], index: &mut usize) -> Option<Color> {
+   match codes.get(*index + 1) {
+      Some(5) => {
+         let color = codes.get(*index + 2).map(| code | palette_color(*code));
+
+         *index += 2;
+         color
+      },
+      Some(2) => {
+         let channels = codes.get(*index + 2..*index + 5);
+
+         *index += 4;
+         channels.map(| rgb | Color { r: rgb[0].min(255) as f32, g: rgb[1].min(255) as f32, b: rgb[2].min(255) as f32 })
+      },
+      _ => None
+   }
+}
+
+fn palette_color(code: u16) -> Color {
+   let (r, g, b) = match code {
+      0..=15 => STANDARD_PALETTE[code as usize],
+      16..=231 => {
+         let cube_index = code - 16;
+         let level = | value: u16 | if value == 0 { 0 } else { (55 + value * 40) as u8 };
+
+         (level(cube_index / 36), level((cube_index / 6) % 6), level(cube_index % 6))
+      },
+      _ => {
+         let gray = (8 + (code.min(255) - 232) * 10) as u8;
+
+         (gray, gray, gray)
+      }
+   };
+
+   Color { r: r as f32, g: g as f32, b: b as f32 }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn split(line: &str) -> Vec<StyledSegment> {
+      split_escape_sequences(line, &mut GraphemeStyle::default())
+   }
+
+   fn text(segments: &[StyledSegment]) -> String {
+      segments.iter().map(| segment | segment.text.as_str()).collect()
+   }
+
+   #[test]
+   fn reads_256_color_foregrounds() {
+      let segments = split("\x1b[38;5;196mred");
+
+      assert_eq!(segments[0].style.and_then(| style | style.foreground), Some(Color { r: 255.0, g: 0.0, b: 0.0 }));
+   }
+
+   #[test]
+   fn reads_256_color_grays() {
+      let segments = split("\x1b[48;5;232mgray");
+
+      assert_eq!(segments[0].style.and_then(| style | style.background), Some(Color { r: 8.0, g: 8.0, b: 8.0 }));
+   }
+
+   #[test]
+   fn reads_true_color_foregrounds() {
+      let segments = split("\x1b[1;38;2;10;20;30mrgb");
+      let style = segments[0].style.unwrap();
+
+      assert_eq!(style.foreground, Some(Color { r: 10.0, g: 20.0, b: 30.0 }));
+      assert!(style.attributes.bold);
+   }
+
+   #[test]
+   fn splits_segments_on_style_changes() {
+      let segments = split("a\x1b[31mb\x1b[0mc");
+
+      assert_eq!(segments.len(), 3);
+      assert!(segments[0].style.is_none());
+      assert!(segments[1].style.is_some());
+      assert!(segments[2].style.is_none());
+   }
+
+   #[test]
+   fn keeps_the_style_across_lines() {
+      let mut style = GraphemeStyle::default();
+
+      split_escape_sequences("\x1b[4mfirst", &mut style);
+      let segments = split_escape_sequences("second", &mut style);
+
+      assert!(segments[0].style.is_some_and(| style | style.attributes.underline));
+   }
+
+   #[test]
+   fn removes_operating_system_commands() {
+      assert_eq!(text(&split("a\x1b]0;title\x07b\x1b]8;;link\x1b\\c")), "abc");
+   }
+
+   #[test]
+   fn removes_unterminated_operating_system_commands() {
+      assert_eq!(text(&split("a\x1b]0;title")), "a");
+   }
+
+   #[test]
+   fn removes_whole_escape_sequences() {
+      assert_eq!(text(&split("red\x1b(Bx\x1b7y\x1b[2Jz")), "redxyz");
+   }
+}
@@ -1,4 +1,8 @@
+use aligner::{color::Color, parser::{grapheme_width, parse_program_colors}, state::{split_characters, AnsiHandling, Border, BorderStyle, ControlCharacters, Direction, Easing, Justify, Outline, Outro, OutroStyle, ProgramMode, ProgramState, RevealOrder, Shadow, ShadowStyle, Transform}, style::TextAttributes};
 use clap::Parser;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{export::ExportFormat, terminal::{ColorChoice, SyncUpdates}};
 
 #[derive(Parser, Debug)]
 pub struct ProgramArguments {
@@ -14,11 +18,134 @@ pub struct ProgramArguments {
    #[arg(short = 'o', long = "horizontally")]
    pub align_horizontally: bool,
 
-   #[arg(short = 'l', long = "line-line", value_name = "milliseconds")]
-   pub line_by_line_mode: Option<u32>,
+   #[arg(short = 'j', long = "justify", value_name = "block|left|center|right")]
+   pub justify: Option<Justify>,
+
+   #[arg(long = "tab-width", value_name = "columns", default_value_t = 8)]
+   pub tab_width: usize,
+
+   #[arg(long = "control-chars", value_name = "strip|escape", default_value = "strip")]
+   pub control_characters: ControlCharacters,
+
+   #[arg(long = "ansi", value_name = "keep|strip|override", default_value = "keep")]
+   pub ansi: AnsiHandling,
+
+   #[arg(short = 'm', long = "markup")]
+   pub markup: bool,
+
+   #[arg(long = "bold")]
+   pub bold: bool,
+
+   #[arg(long = "dim")]
+   pub dim: bool,
+
+   #[arg(long = "italic")]
+   pub italic: bool,
+
+   #[arg(long = "underline")]
+   pub underline: bool,
+
+   #[arg(long = "blink")]
+   pub blink: bool,
+
+   #[arg(long = "reverse")]
+   pub reverse: bool,
+
+   #[arg(long = "strikethrough")]
+   pub strikethrough: bool,
+
+   #[arg(short = 'b', long = "border", value_name = "single|double|rounded|heavy|ascii|custom")]
+   pub border: Option<BorderStyle>,
+
+   #[arg(long = "border-chars", value_name = "8 characters", requires = "border")]
+   pub border_characters: Option<String>,
+
+   #[arg(long = "border-padding", value_name = "vertical horizontal", num_args = 1..=2)]
+   pub border_padding: Vec<usize>,
+
+   #[arg(long = "border-title", value_name = "title")]
+   pub border_title: Option<String>,
+
+   #[arg(long = "border-colors", value_name = "hexadecimal colors", num_args = 1..)]
+   pub border_colors: Option<Vec<String>>,
+
+   #[arg(long = "shadow", value_name = "drop|extrude")]
+   pub shadow: Option<ShadowStyle>,
+
+   #[arg(long = "shadow-direction", value_name = "direction", default_value = "down-right")]
+   pub shadow_direction: Direction,
+
+   #[arg(long = "shadow-distance", value_name = "cells", default_value_t = 1)]
+   pub shadow_distance: usize,
+
+   #[arg(long = "shadow-color", value_name = "hexadecimal color", default_value = "#3A3A3A")]
+   pub shadow_color: String,
+
+   #[arg(long = "transform", value_name = "flip-h|flip-v|rotate90|rotate180|rotate270")]
+   pub transform: Option<Transform>,
 
-   #[arg(short = 'c', long = "char-char", value_name = "milliseconds")]
-   pub char_by_char_mode: Option<u32>,
+   #[arg(long = "outline")]
+   pub outline: bool,
+
+   #[arg(long = "hollow")]
+   pub hollow: bool,
+
+   #[arg(long = "outline-char", value_name = "character", default_value = "░")]
+   pub outline_character: String,
+
+   #[arg(long = "outline-color", value_name = "hexadecimal color")]
+   pub outline_color: Option<String>,
+
+   #[arg(short = 'l', long = "line-line", value_name = "milliseconds", num_args = 0..=1)]
+   pub line_by_line_mode: Option<Option<u32>>,
+
+   #[arg(short = 'c', long = "char-char", value_name = "milliseconds", num_args = 0..=1)]
+   pub char_by_char_mode: Option<Option<u32>>,
+
+   #[arg(long = "word-word", value_name = "milliseconds", num_args = 0..=1)]
+   pub word_by_word_mode: Option<Option<u32>>,
+
+   #[arg(long = "column-column", value_name = "milliseconds", num_args = 0..=1)]
+   pub column_by_column_mode: Option<Option<u32>>,
+
+   #[arg(long = "fade", value_name = "milliseconds", num_args = 0..=1)]
+   pub fade_mode: Option<Option<u32>>,
+
+   #[arg(long = "fade-out", requires = "fade_mode")]
+   pub fade_out: bool,
+
+   #[arg(long = "decode", value_name = "milliseconds", num_args = 0..=1)]
+   pub decode_mode: Option<Option<u32>>,
+
+   #[arg(long = "decode-order", value_name = "order", default_value = "left-to-right")]
+   pub decode_order: RevealOrder,
+
+   #[arg(long = "decode-chars", value_name = "characters")]
+   pub decode_characters: Option<String>,
+
+   #[arg(long = "seed", value_name = "number")]
+   pub seed: Option<u64>,
+
+   #[arg(long = "wipe", value_name = "milliseconds", num_args = 0..=1)]
+   pub wipe_mode: Option<Option<u32>>,
+
+   #[arg(long = "slide", value_name = "milliseconds", num_args = 0..=1)]
+   pub slide_mode: Option<Option<u32>>,
+
+   #[arg(long = "dissolve", value_name = "milliseconds", num_args = 0..=1)]
+   pub dissolve_mode: Option<Option<u32>>,
+
+   #[arg(long = "duration", value_name = "milliseconds")]
+   pub duration: Option<u64>,
+
+   #[arg(long = "outro", value_name = "style")]
+   pub outro: Option<OutroStyle>,
+
+   #[arg(long = "outro-duration", value_name = "milliseconds")]
+   pub outro_duration: Option<u64>,
+
+   #[arg(long = "easing", value_name = "curve", default_value = "linear")]
+   pub easing: Easing,
 
    #[arg(short = 's', long = "colors", value_name = "hexadecimal colors", num_args=1..)]
    pub colors: Option<Vec<String>>,
@@ -33,5 +160,230 @@ pub struct ProgramArguments {
    pub disable_stdout: bool,
 
    #[arg(short = 'n', long = "control-server", value_name = "address:port")]
-   pub control_server: Option<String>
-}
\ No newline at end of file
+   pub control_server: Option<String>,
+
+   #[arg(long = "output", value_name = "file")]
+   pub output: Option<String>,
+
+   #[arg(long = "format", value_name = "ansi|html|svg")]
+   pub export_format: Option<ExportFormat>,
+
+   #[arg(long = "export-font", value_name = "font family", default_value = "monospace")]
+   pub export_font: String,
+
+   #[arg(long = "export-background", value_name = "hexadecimal color", default_value = "#000000")]
+   pub export_background: String,
+
+   #[arg(long = "svg-blocks")]
+   pub svg_blocks: bool,
+
+   #[arg(long = "width", value_name = "columns")]
+   pub width: Option<u16>,
+
+   #[arg(long = "height", value_name = "rows")]
+   pub height: Option<u16>,
+
+   #[arg(long = "record", value_name = "file")]
+   pub record: Option<String>,
+
+   #[arg(long = "color", value_name = "when", default_value = "auto")]
+   pub color: ColorChoice,
+
+   #[arg(long = "sync-updates", value_name = "when", default_value = "auto")]
+   pub sync_updates: SyncUpdates
+}
+
+pub fn generate_program_state(arguments: &ProgramArguments) -> Result<ProgramState, String> {
+   let colors = match &arguments.colors {
+      Some(colors) => {
+         parse_program_colors(colors).map_err(| error | format!("Error while trying to parse the colors argument: {}", error))?
+      },
+      None => {
+         vec![Color { r: 255.0, g: 255.0, b: 255.0 }]
+      }
+   };
+
+   let mut mode = ProgramMode::Flash;
+   let mut delay = 0;
+
+   // When several mode flags are given, the one listed last here wins, whatever their order on the
+   // command line. A mode flag can only be left without a value when --duration gives the animation
+   // its length.
+   let mode_flags = [
+      (arguments.line_by_line_mode, ProgramMode::LineByLine, "--line-line"),
+      (arguments.char_by_char_mode, ProgramMode::CharByChar, "--char-char"),
+      (arguments.word_by_word_mode, ProgramMode::WordByWord, "--word-word"),
+      (arguments.column_by_column_mode, ProgramMode::ColumnByColumn, "--column-column"),
+      (arguments.fade_mode, ProgramMode::Fade, "--fade"),
+      (arguments.decode_mode, ProgramMode::Decode, "--decode"),
+      (arguments.wipe_mode, ProgramMode::Wipe, "--wipe"),
+      (arguments.slide_mode, ProgramMode::Slide, "--slide"),
+      (arguments.dissolve_mode, ProgramMode::Dissolve, "--dissolve")
+   ];
+
+   for (value, flag_mode, flag) in mode_flags {
+      if let Some(value) = value {
+         mode = flag_mode;
+         delay = match value {
+            Some(delay) => delay,
+            None if arguments.duration.is_some() => 0,
+            None => return Err(format!("The {} flag needs a value in milliseconds unless --duration is given.", flag))
+         };
+      }
+   }
+
+   let mut gradient_angle = 0;
+
+   if let Some(angle) = arguments.angle {
+      gradient_angle = angle;
+   }
+
+   let mut builder = ProgramState::builder()
+                        .angle(gradient_angle as f32)
+                        .colors(colors)
+                        .mode(mode, delay.into())
+                        .align_vertically(arguments.align_vertically)
+                        .align_horizontally(arguments.align_horizontally)
+                        .tab_width(arguments.tab_width)
+                        .control_characters(arguments.control_characters)
+                        .ansi(arguments.ansi)
+                        .markup(arguments.markup)
+                        .reveal_order(arguments.decode_order)
+                        .easing(arguments.easing)
+                        .attributes(TextAttributes {
+                           bold: arguments.bold,
+                           dim: arguments.dim,
+                           italic: arguments.italic,
+                           underline: arguments.underline,
+                           blink: arguments.blink,
+                           reverse: arguments.reverse,
+                           strikethrough: arguments.strikethrough
+                        });
+
+   if let Some(justify) = arguments.justify {
+      builder = builder.justify(justify);
+   }
+
+   if let Some(border_style) = arguments.border {
+      builder = builder.border(generate_border(border_style, arguments)?);
+   }
+
+   if let Some(shadow_style) = arguments.shadow {
+      builder = builder.shadow(generate_shadow(shadow_style, arguments)?);
+   }
+
+   if arguments.outline || arguments.hollow {
+      builder = builder.outline(generate_outline(arguments)?);
+   }
+
+   if let Some(transform) = arguments.transform {
+      builder = builder.transform(transform);
+   }
+
+   if let Some(characters) = &arguments.decode_characters {
+      builder = builder.decode_characters(generate_decode_characters(characters)?);
+   }
+
+   if let Some(seed) = arguments.seed {
+      builder = builder.seed(seed);
+   }
+
+   if let Some(duration) = arguments.duration {
+      builder = builder.duration(duration);
+   }
+
+   // The --fade-out flag predates the outros and fades out over the same time as the fade in.
+   if let Some(style) = arguments.outro {
+      builder = builder.outro(Outro { style, duration: arguments.outro_duration });
+   } else if arguments.fade_out {
+      builder = builder.outro(Outro { style: OutroStyle::Fade, duration: arguments.outro_duration.or(arguments.duration).or(arguments.fade_mode.flatten().map(u64::from)) });
+   }
+
+   Ok(builder.build())
+}
+
+fn generate_border(border_style: BorderStyle, arguments: &ProgramArguments) -> Result<Border, String> {
+   if arguments.border_characters.is_some() && border_style != BorderStyle::Custom {
+      return Err("The --border-chars flag can only be used with a custom border.".to_string());
+   }
+
+   let characters = match border_style {
+      BorderStyle::Single => "┌─┐││└─┘",
+      BorderStyle::Double => "╔═╗║║╚═╝",
+      BorderStyle::Rounded => "╭─╮││╰─╯",
+      BorderStyle::Heavy => "┏━┓┃┃┗━┛",
+      BorderStyle::Ascii => "+-+||+-+",
+      BorderStyle::Custom => arguments.border_characters.as_deref().unwrap_or("")
+   };
+
+   let characters = split_characters(characters);
+
+   if characters.len() != 8 || characters.iter().any(| grapheme | grapheme_width(grapheme) != 1) {
+      return Err("A custom border needs exactly 8 single-column characters in --border-chars.".to_string());
+   }
+
+   let (vertical_padding, horizontal_padding) = match arguments.border_padding.as_slice() {
+      [] => (0, 1),
+      [padding] => (*padding, *padding),
+      [vertical, horizontal, ..] => (*vertical, *horizontal)
+   };
+
+   let colors = match &arguments.border_colors {
+      Some(colors) => Some(parse_program_colors(colors).map_err(| error | format!("Error while trying to parse the border colors: {}", error))?),
+      None => None
+   };
+
+   Ok(Border { characters, vertical_padding, horizontal_padding, title: arguments.border_title.clone(), colors })
+}
+
+fn generate_shadow(shadow_style: ShadowStyle, arguments: &ProgramArguments) -> Result<Shadow, String> {
+   let (x_direction, y_direction) = match arguments.shadow_direction {
+      Direction::Up => (0, -1),
+      Direction::Down => (0, 1),
+      Direction::Left => (-1, 0),
+      Direction::Right => (1, 0),
+      Direction::UpLeft => (-1, -1),
+      Direction::UpRight => (1, -1),
+      Direction::DownLeft => (-1, 1),
+      Direction::DownRight => (1, 1)
+   };
+
+   let color = parse_program_colors(&vec![arguments.shadow_color.clone()])
+                  .map_err(| error | format!("Error while trying to parse the shadow color: {}", error))?[0];
+
+   let shadow = match shadow_style {
+      ShadowStyle::Drop => {
+         let distance = arguments.shadow_distance as isize;
+
+         Shadow { x_step: x_direction * distance, y_step: y_direction * distance, layers: 1, color }
+      },
+      ShadowStyle::Extrude => Shadow { x_step: x_direction, y_step: y_direction, layers: arguments.shadow_distance, color }
+   };
+
+   Ok(shadow)
+}
+
+fn generate_outline(arguments: &ProgramArguments) -> Result<Outline, String> {
+   let character = arguments.outline_character.clone();
+
+   if character.graphemes(true).count() != 1 || grapheme_width(&character) != 1 {
+      return Err("The outline character must be a single-column character.".to_string());
+   }
+
+   let color = match &arguments.outline_color {
+      Some(color) => Some(parse_program_colors(&vec![color.clone()]).map_err(| error | format!("Error while trying to parse the outline color: {}", error))?[0]),
+      None => None
+   };
+
+   Ok(Outline { character, color, hollow: arguments.hollow })
+}
+
+fn generate_decode_characters(characters: &str) -> Result<Vec<String>, String> {
+   let characters = split_characters(characters);
+
+   if characters.is_empty() || characters.iter().any(| grapheme | grapheme_width(grapheme) != 1) {
+      return Err("The --decode-chars flag needs at least one character, and every character must take a single column.".to_string());
+   }
+
+   Ok(characters)
+}
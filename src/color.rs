@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
    pub r: f32,
    pub g: f32,
@@ -0,0 +1,439 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{color::{interpolate_multi_color, lerp_color, Color}, display::justify_line, parser::{grapheme_width, line_width, Grapheme, ParsedMessage}, state::{Border, Justify, Outline, ProgramState, Shadow}, style::GraphemeStyle, transform::transform_message};
+
+const TOP_LEFT: usize = 0;
+const TOP: usize = 1;
+const TOP_RIGHT: usize = 2;
+const LEFT: usize = 3;
+const RIGHT: usize = 4;
+const BOTTOM_LEFT: usize = 5;
+const BOTTOM: usize = 6;
+const BOTTOM_RIGHT: usize = 7;
+
+#[derive(Clone)]
+pub enum Cell {
+   Empty,
+   Filled(Grapheme),
+   Continuation
+}
+
+// Messages without decorations are returned as None, so their lines are justified while being
+// laid out on the frame. Otherwise only the first decoration justifies the lines, and the next
+// ones receive whole rows.
+pub fn decorate_message(message: &ParsedMessage, state: &ProgramState) -> Option<ParsedMessage> {
+   if state.transform.is_none() && state.outline.is_none() && state.shadow.is_none() && state.border.is_none() {
+      return None;
+   }
+
+   // Message files usually end with a line feed, which would leave an empty row in the decoration.
+   let trimmed_message;
+   let message = match message.lines.split_last() {
+      Some((last_line, lines)) if last_line.is_empty() && !lines.is_empty() => {
+         trimmed_message = ParsedMessage { max_line_size: message.max_line_size, lines: lines.to_vec() };
+         &trimmed_message
+      },
+      _ => message
+   };
+
+   let mut decorated: Option<ParsedMessage> = None;
+
+   if let Some(transform) = state.transform {
+      let (source, justify) = decoration_source(&decorated, message, state.justify);
+
+      decorated = Some(transform_message(source, transform, justify));
+   }
+
+   if let Some(outline) = &state.outline {
+      let (source, justify) = decoration_source(&decorated, message, state.justify);
+
+      decorated = Some(add_outline(source, outline, justify));
+   }
+
+   if let Some(shadow) = &state.shadow {
+      let (source, justify) = decoration_source(&decorated, message, state.justify);
+
+      decorated = Some(add_shadow(source, shadow, justify));
+   }
+
+   if let Some(border) = &state.border {
+      let (source, justify) = decoration_source(&decorated, message, state.justify);
+
+      decorated = Some(add_border(source, border, justify, state));
+   }
+
+   decorated
+}
+
+fn decoration_source<'a>(decorated: &'a Option<ParsedMessage>, message: &'a ParsedMessage, justify: Justify) -> (&'a ParsedMessage, Justify) {
+   match decorated {
+      Some(decorated) => (decorated, Justify::Block),
+      None => (message, justify)
+   }
+}
+
+fn add_border(message: &ParsedMessage, border: &Border, justify: Justify, state: &ProgramState) -> ParsedMessage {
+   let title: Vec<Grapheme> = border.title.as_deref().unwrap_or("")
+                                     .graphemes(true)
+                                     .filter(| grapheme | !grapheme.chars().any(char::is_control))
+                                     .map(| grapheme | Grapheme { value: grapheme.to_string(), style: None, glyph: None })
+                                     .collect();
+
+   let title_width = if title.is_empty() { 0 } else { line_width(&title) + 3 };
+   let inner_width = (message.max_line_size + border.horizontal_padding * 2).max(title_width);
+   let content_width = inner_width - border.horizontal_padding * 2;
+   let total_height = message.lines.len() + border.vertical_padding * 2 + 2;
+
+   let mut lines: Vec<Vec<Grapheme>> = Vec::new();
+   let mut top_line = vec![border_grapheme(border, TOP_LEFT)];
+
+   if !title.is_empty() {
+      top_line.push(border_grapheme(border, TOP));
+      top_line.extend(blank_graphemes(1));
+      top_line.extend(title);
+      top_line.extend(blank_graphemes(1));
+   }
+
+   while line_width(&top_line) < inner_width + 1 {
+      top_line.push(border_grapheme(border, TOP));
+   }
+
+   top_line.push(border_grapheme(border, TOP_RIGHT));
+   lines.push(top_line);
+
+   for _ in 0..border.vertical_padding {
+      lines.push(bordered_line(&[], 0, inner_width, border));
+   }
+
+   let content_offset = match state.justify {
+      Justify::Center => (content_width - message.max_line_size) / 2,
+      Justify::Right => content_width - message.max_line_size,
+      _ => 0
+   };
+
+   for line in &message.lines {
+      let (line_offset, justified_line) = justify_line(line, message.max_line_size, justify);
+
+      lines.push(bordered_line(justified_line, border.horizontal_padding + content_offset + line_offset, inner_width, border));
+   }
+
+   for _ in 0..border.vertical_padding {
+      lines.push(bordered_line(&[], 0, inner_width, border));
+   }
+
+   let mut bottom_line = vec![border_grapheme(border, BOTTOM_LEFT)];
+
+   bottom_line.extend(std::iter::repeat_n(border_grapheme(border, BOTTOM), inner_width));
+   bottom_line.push(border_grapheme(border, BOTTOM_RIGHT));
+   lines.push(bottom_line);
+
+   color_border(&mut lines, inner_width + 2, total_height, border, state);
+
+   ParsedMessage { max_line_size: inner_width + 2, lines }
+}
+
+fn bordered_line(content: &[Grapheme], offset: usize, inner_width: usize, border: &Border) -> Vec<Grapheme> {
+   let mut line = vec![border_grapheme(border, LEFT)];
+
+   line.extend(blank_graphemes(offset));
+   line.extend_from_slice(content);
+   line.extend(blank_graphemes(inner_width - offset - line_width(content)));
+   line.push(border_grapheme(border, RIGHT));
+
+   line
+}
+
+fn color_border(lines: &mut [Vec<Grapheme>], width: usize, height: usize, border: &Border, state: &ProgramState) {
+   let colors = match &border.colors {
+      Some(colors) => colors,
+      None => return
+   };
+
+   let last_line = lines.len() - 1;
+
+   for (y, line) in lines.iter_mut().enumerate() {
+      let mut x = 0;
+      let last_grapheme = line.len() - 1;
+
+      for (index, grapheme) in line.iter_mut().enumerate() {
+         let is_border = y == 0 || y == last_line || index == 0 || index == last_grapheme;
+
+         if is_border {
+            let color = interpolate_multi_color(x as f32, y as f32, width as f32, height as f32, state.angle, colors);
+
+            grapheme.style = Some(GraphemeStyle { foreground: Some(color), ..Default::default() });
+         }
+
+         x += grapheme_width(&grapheme.value);
+      }
+   }
+}
+
+// The built-in font draws its strokes with full blocks and shades them with box-drawing
+// characters, so only the blocks are the body of the glyphs when the message has any. The gaps
+// within a glyph, like the space between the legs of an H, are part of it and never outlined.
+fn add_outline(message: &ParsedMessage, outline: &Outline, justify: Justify) -> ParsedMessage {
+   let source = to_canvas(message, justify);
+   let width = message.max_line_size + 2;
+   let height = message.lines.len() + 2;
+   let has_blocks = message.lines.iter().flatten().any(| grapheme | grapheme.value == "█");
+   let mut filled = vec![vec![false; width]; height];
+   let mut inside = vec![vec![false; width]; height];
+   let mut canvas = vec![vec![Cell::Empty; width]; height];
+
+   for_each_visible_cell(&source, | x, y, grapheme | {
+      let grapheme_columns = grapheme_width(&grapheme.value).max(1);
+
+      inside[y + 1][x + 1..x + 1 + grapheme_columns].fill(true);
+
+      if !has_blocks || grapheme.value == "█" {
+         filled[y + 1][x + 1..x + 1 + grapheme_columns].fill(true);
+      }
+   });
+
+   for (y, row) in source.iter().enumerate() {
+      for (first_x, last_x) in glyph_spans(row) {
+         inside[y + 1][first_x + 1..last_x + 1].fill(true);
+      }
+   }
+
+   let outline_style = outline.color.map(| color | GraphemeStyle { foreground: Some(color), ..Default::default() });
+
+   for y in 0..height {
+      for x in 0..width {
+         // Hollow outlines follow the inner contour of the glyphs, the blocks next to anything
+         // else, while regular ones surround the glyphs and their shading from the outside.
+         let is_outline = if outline.hollow {
+            filled[y][x] && [(0, 1), (2, 1), (1, 0), (1, 2)].iter().any(| (dy, dx) | !filled[y + dy - 1][x + dx - 1])
+         } else {
+            !inside[y][x] && (y.saturating_sub(1)..(y + 2).min(height)).any(| neighbor_y | {
+               (x.saturating_sub(1)..(x + 2).min(width)).any(| neighbor_x | inside[neighbor_y][neighbor_x])
+            })
+         };
+
+         if is_outline {
+            canvas[y][x] = Cell::Filled(Grapheme { value: outline.character.clone(), style: outline_style, glyph: None });
+         }
+      }
+   }
+
+   if !outline.hollow {
+      for_each_visible_cell(&source, | x, y, grapheme | {
+         put_grapheme(&mut canvas[y + 1], x + 1, grapheme.clone());
+      });
+   }
+
+   from_canvas(canvas)
+}
+
+// Returns the columns covered by each glyph of the built-in font on a canvas row, from its
+// first visible cell up to the end of its last one.
+fn glyph_spans(row: &[Cell]) -> Vec<(usize, usize)> {
+   let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+
+   for (x, cell) in row.iter().enumerate() {
+      let grapheme = match cell {
+         Cell::Filled(grapheme) if !grapheme.is_blank() => grapheme,
+         _ => continue
+      };
+
+      if let Some(glyph) = grapheme.glyph {
+         let end = x + grapheme_width(&grapheme.value).max(1);
+
+         match spans.iter_mut().find(| span | span.0 == glyph) {
+            Some(span) => span.2 = end,
+            None => spans.push((glyph, x, end))
+         }
+      }
+   }
+
+   spans.into_iter().map(| (_, first_x, last_x) | (first_x, last_x)).collect()
+}
+
+fn add_shadow(message: &ParsedMessage, shadow: &Shadow, justify: Justify) -> ParsedMessage {
+   let x_extent = shadow.x_step.unsigned_abs() * shadow.layers;
+   let y_extent = shadow.y_step.unsigned_abs() * shadow.layers;
+   let text_x = if shadow.x_step < 0 { x_extent } else { 0 };
+   let text_y = if shadow.y_step < 0 { y_extent } else { 0 };
+   let source = to_canvas(message, justify);
+   let mut canvas = vec![vec![Cell::Empty; message.max_line_size + x_extent]; message.lines.len() + y_extent];
+
+   for layer in (1..=shadow.layers).rev() {
+      let darkness = (layer - 1) as f32 / shadow.layers as f32 * 0.5;
+      let color = lerp_color(shadow.color, Color { r: 0.0, g: 0.0, b: 0.0 }, darkness);
+      let x_offset = (text_x as isize + shadow.x_step * layer as isize) as usize;
+      let y_offset = (text_y as isize + shadow.y_step * layer as isize) as usize;
+
+      for_each_visible_cell(&source, | x, y, grapheme | {
+         let shadow_grapheme = Grapheme { value: grapheme.value.clone(), style: Some(GraphemeStyle { foreground: Some(color), ..Default::default() }), glyph: grapheme.glyph };
+
+         put_grapheme(&mut canvas[y + y_offset], x + x_offset, shadow_grapheme);
+      });
+   }
+
+   for_each_visible_cell(&source, | x, y, grapheme | {
+      put_grapheme(&mut canvas[y + text_y], x + text_x, grapheme.clone());
+   });
+
+   from_canvas(canvas)
+}
+
+pub fn to_canvas(message: &ParsedMessage, justify: Justify) -> Vec<Vec<Cell>> {
+   message.lines.iter().map(| line | {
+      let mut row = vec![Cell::Empty; message.max_line_size];
+      let (mut x, justified_line) = justify_line(line, message.max_line_size, justify);
+
+      for grapheme in justified_line {
+         put_grapheme(&mut row, x, grapheme.clone());
+         x += grapheme_width(&grapheme.value);
+      }
+
+      row
+   }).collect()
+}
+
+pub fn from_canvas(canvas: Vec<Vec<Cell>>) -> ParsedMessage {
+   let max_line_size = canvas.first().map_or(0, | row | row.len());
+   let lines = canvas.into_iter().map(| row | {
+      row.into_iter().filter_map(| cell | match cell {
+         Cell::Empty => Some(Grapheme::blank()),
+         Cell::Filled(grapheme) => Some(grapheme),
+         Cell::Continuation => None
+      }).collect()
+   }).collect();
+
+   ParsedMessage { max_line_size, lines }
+}
+
+pub fn for_each_visible_cell(canvas: &[Vec<Cell>], mut action: impl FnMut(usize, usize, &Grapheme)) {
+   for (y, row) in canvas.iter().enumerate() {
+      for (x, cell) in row.iter().enumerate() {
+         if let Cell::Filled(grapheme) = cell {
+            if !grapheme.is_blank() {
+               action(x, y, grapheme);
+            }
+         }
+      }
+   }
+}
+
+pub fn put_grapheme(row: &mut [Cell], x: usize, grapheme: Grapheme) {
+   let width = grapheme_width(&grapheme.value).max(1);
+
+   if x + width > row.len() {
+      return;
+   }
+
+   for column in x..x + width {
+      match row[column] {
+         Cell::Continuation if column > 0 => row[column - 1] = Cell::Filled(Grapheme::blank()),
+         Cell::Filled(ref existing) if grapheme_width(&existing.value) > 1 && column + 1 < row.len() => row[column + 1] = Cell::Empty,
+         _ => {}
+      }
+   }
+
+   row[x] = Cell::Filled(grapheme);
+
+   row[x + 1..x + width].fill(Cell::Continuation);
+}
+
+fn border_grapheme(border: &Border, position: usize) -> Grapheme {
+   Grapheme { value: border.characters[position].clone(), style: None, glyph: None }
+}
+
+fn blank_graphemes(count: usize) -> impl Iterator<Item = Grapheme> {
+   std::iter::repeat_n(Grapheme::blank(), count)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{parser::parse_message, typewriter::generate_message};
+
+   fn outlined(text: &str, hollow: bool) -> (ParsedMessage, ParsedMessage) {
+      let message = generate_message(text.to_string());
+      let state = ProgramState::builder().outline(Outline { character: "░".to_string(), color: None, hollow }).build();
+      let decorated = decorate_message(&message, &state).unwrap();
+
+      (message, decorated)
+   }
+
+   fn rows(message: &ParsedMessage) -> Vec<Vec<String>> {
+      to_canvas(message, Justify::Block).into_iter().map(| row | {
+         row.into_iter().map(| cell | match cell {
+            Cell::Filled(grapheme) => grapheme.value,
+            _ => " ".to_string()
+         }).collect()
+      }).collect()
+   }
+
+   fn bordered(text: &str, state: ProgramState) -> Vec<String> {
+      let message = parse_message(text.to_string(), &state);
+      let decorated = decorate_message(&message, &state).unwrap();
+
+      decorated.lines.iter().map(| line | line.iter().map(| grapheme | grapheme.value.as_str()).collect()).collect()
+   }
+
+   fn single_border(vertical_padding: usize, title: Option<&str>) -> Border {
+      let characters = "┌─┐││└─┘".chars().map(| c | c.to_string()).collect();
+
+      Border { characters, vertical_padding, horizontal_padding: 1, title: title.map(str::to_string), colors: None }
+   }
+
+   #[test]
+   fn hollow_outlines_keep_glyphs_legible() {
+      let (message, decorated) = outlined("H", true);
+      let source = rows(&message);
+      let outline = rows(&decorated);
+
+      for (y, row) in outline.iter().enumerate() {
+         for (x, value) in row.iter().enumerate() {
+            let source_value = source.get(y.wrapping_sub(1)).and_then(| row | row.get(x.wrapping_sub(1))).map_or(" ", | value | value.as_str());
+
+            if value == "░" {
+               assert_eq!(source_value, "█", "outline drawn outside the glyph body at {}, {}", x, y);
+            }
+         }
+      }
+
+      for (y, row) in source.iter().enumerate() {
+         if row.iter().any(| value | value == "█") {
+            assert!(outline[y + 1].iter().any(| value | value == "░"), "row {} of the glyph vanished", y);
+         }
+      }
+
+      assert_eq!(outline[2][4], " ");
+      assert_eq!(outline[2][5], " ");
+   }
+
+   #[test]
+   fn outlines_leave_the_gaps_inside_glyphs_empty() {
+      let (_, decorated) = outlined("H", false);
+      let outline = rows(&decorated);
+
+      assert!(outline[0].iter().all(| value | value == "░"));
+      assert_eq!(outline[1][4], " ");
+      assert_eq!(outline[1][5], " ");
+   }
+
+   #[test]
+   fn borders_surround_the_message_with_padding() {
+      let state = ProgramState::builder().border(single_border(1, None)).build();
+
+      assert_eq!(bordered("ab\nc", state), ["┌────┐", "│    │", "│ ab │", "│ c  │", "│    │", "└────┘"]);
+   }
+
+   #[test]
+   fn border_titles_widen_the_border() {
+      let state = ProgramState::builder().border(single_border(0, Some("Hi"))).build();
+
+      assert_eq!(bordered("a", state), ["┌─ Hi ┐", "│ a   │", "└─────┘"]);
+   }
+
+   #[test]
+   fn border_titles_keep_the_message_justified() {
+      let state = ProgramState::builder().border(single_border(0, Some("Hello"))).justify(Justify::Center).build();
+
+      assert_eq!(bordered("a", state), ["┌─ Hello ┐", "│   a    │", "└────────┘"]);
+   }
+}
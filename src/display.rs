@@ -1,95 +1,156 @@
-use std::{io::{stdout, Error, Write}, ops::AddAssign, thread::sleep, time::Duration};
+use std::io::{Error, Write};
 
-use crossterm::{style::{ Color, ResetColor, SetForegroundColor }, ExecutableCommand, QueueableCommand};
+use crossterm::{cursor::MoveTo, style::{ Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor }, QueueableCommand};
 
-use crate::{ color::interpolate_multi_color, parser::ParsedMessage, state::{ProgramMode, ProgramState}, terminal::{move_cursor_right, move_cursor_to, TerminalSize}};
+use crate::{parser::{line_width, Grapheme}, render::{Frame, FrameCell, TerminalSize}, style::{GraphemeStyle, TextAttributes}, state::{Justify, ProgramState}};
 
-pub fn print_message(message: &ParsedMessage, state: &ProgramState, dimensions: &TerminalSize, animate_draw: bool, stdout_print: bool) {
-   if stdout_print == false {
-      move_cursor_to(0, 0).expect("Failed to set the cursor position. Exiting.");
+pub fn write_frame(frame: &Frame, output: &mut impl Write, colored: bool) -> Result<(), Error> {
+   write_rows(frame, output, colored, "\n")
+}
+
+// The standard output mode keeps the layout of the original line printer: vertically aligned
+// messages start two rows lower, and every row ends with a carriage return and a line feed.
+pub fn print_frame(frame: &Frame, state: &ProgramState, output: &mut impl Write, colored: bool) -> Result<(), Error> {
+   if state.align_vertically {
+      write!(output, "\r\n\r\n")?;
    }
 
-   if state.align_vertically == true {
-      add_top_padding(&dimensions, message.lines.len(), stdout_print);
+   write_rows(frame, output, colored, "\r\n")
+}
+
+fn write_rows(frame: &Frame, output: &mut impl Write, colored: bool, line_ending: &str) -> Result<(), Error> {
+   for row in &frame.rows {
+      write_row(output, row, frame.width, false, colored)?;
+      write!(output, "{}", line_ending)?;
    }
 
-   for line in message.lines.iter().enumerate() {
-      if state.align_horizontally == true {
-         add_left_padding(&dimensions, line.1.len());
-      }
+   if colored {
+      output.queue(SetAttribute(Attribute::Reset))?;
+   }
 
-      let color_index = if state.align_horizontally {
-         (message.max_line_size - line.1.len()) / 2
-      } else {
-         0 
-      };
+   output.flush()
+}
 
-      print_line(line.1, color_index, line.0, message.max_line_size, message.lines.len(), state, animate_draw);
+pub fn draw_frame(frame: &Frame, dimensions: &TerminalSize, output: &mut impl Write) -> Result<(), Error> {
+   let blank_row = vec![None; frame.width];
 
-      if state.mode == ProgramMode::LineByLine && animate_draw == true {
-         sleep(Duration::from_millis(state.animation_delay));
-      }
-   }
+   for y in 0..dimensions.height as usize {
+      let row = frame.rows.get(y).unwrap_or(&blank_row);
+
+      output.queue(MoveTo(0, y as u16))?;
 
-   if state.align_vertically == true && stdout_print == true {
-      add_bottom_padding(&dimensions, message.lines.len());
+      write_row(output, row, frame.width, true, true)?;
    }
+
+   output.flush()
 }
 
-fn print_line(line: &Vec<String>, mut color_index: usize, line_number: usize, max_size: usize, lines_count: usize, state: &ProgramState, animate_draw: bool) {
-   for grapheme in line {
-      let color = interpolate_multi_color(color_index as f32, line_number as f32, max_size as f32, lines_count as f32, state.angle.into(), &state.colors);
+fn write_row(output: &mut impl Write, row: &[Option<FrameCell>], width: usize, fill: bool, colored: bool) -> Result<(), Error> {
+   let end = if fill {
+      width
+   } else {
+      row.iter().rposition(| cell | cell.as_ref().is_some_and(| cell | !cell.is_blank())).map_or(0, | index | index + 1)
+   };
+
+   let mut applied_style = GraphemeStyle::default();
+   let mut x = 0;
 
-      let mut stdout = stdout();
+   while x < end {
+      match &row[x] {
+         Some(cell) => {
+            let style = GraphemeStyle { foreground: Some(cell.foreground), background: cell.background, attributes: cell.attributes };
 
-      stdout.queue(SetForegroundColor(Color::Rgb { r: color.r as u8, g: color.g as u8, b: color.b as u8 })).expect("Failed to set the foreground color. Exiting.");
+            if colored {
+               apply_style(output, &style, &mut applied_style)?;
+            }
 
-      print!("{}", grapheme);
+            write!(output, "{}", cell.value)?;
 
-      if animate_draw == true && state.mode == ProgramMode::CharByChar {
-         sleep(Duration::from_millis(state.animation_delay));
-      }
+            x += cell.width();
+         },
+         None => {
+            if has_decorations(&applied_style) {
+               output.queue(SetAttribute(Attribute::Reset))?;
+               applied_style = GraphemeStyle::default();
+            }
 
-      color_index.add_assign(1);
+            write!(output, " ")?;
+
+            x += 1;
+         }
+      }
+   }
 
-      stdout.flush().unwrap_or(());
+   if has_decorations(&applied_style) {
+      output.queue(SetAttribute(Attribute::Reset))?;
    }
 
-   println!("\r");
+   Ok(())
 }
 
-fn add_top_padding(dimensions: &TerminalSize, message_lines: usize, stdout_print: bool) {
-   let y_print_coordinate = ((dimensions.height / 2) - (message_lines as u16 / 2)) + 2;
+fn apply_style(output: &mut impl Write, style: &GraphemeStyle, applied_style: &mut GraphemeStyle) -> Result<(), Error> {
+   if style.background != applied_style.background || style.attributes != applied_style.attributes {
+      if has_decorations(applied_style) {
+         output.queue(SetAttribute(Attribute::Reset))?;
+         applied_style.foreground = None;
+      }
 
-   if stdout_print {
-      for _ in 0..y_print_coordinate {
-         println!("");
+      for attribute in terminal_attributes(&style.attributes) {
+         output.queue(SetAttribute(attribute))?;
       }
-   } else {
-      move_cursor_to(0, y_print_coordinate).expect("Failed to set the cursor position. Exiting.")
-   }
-}
 
-fn add_bottom_padding(dimensions: &TerminalSize, line_count: usize) {
-   let y_print_coordinate = (dimensions.height as usize / 2) - (line_count / 2);
-   let end_stuffing  = dimensions.height as usize - y_print_coordinate - line_count;
+      if let Some(background) = style.background {
+         output.queue(SetBackgroundColor(to_terminal_color(background)))?;
+      }
+   }
 
-   for _ in 0..end_stuffing {
-      println!("");
+   if let Some(foreground) = style.foreground {
+      if applied_style.foreground != Some(foreground) {
+         output.queue(SetForegroundColor(to_terminal_color(foreground)))?;
+      }
    }
+
+   *applied_style = *style;
+
+   Ok(())
+}
+
+fn has_decorations(style: &GraphemeStyle) -> bool {
+   style.background.is_some() || style.attributes != TextAttributes::default()
 }
 
-fn add_left_padding(dimensions: &TerminalSize, line_length: usize) {
-   let message_x_offset = (dimensions.width / 2).checked_sub(line_length as u16 / 2).unwrap_or(0);
+fn terminal_attributes(attributes: &TextAttributes) -> Vec<Attribute> {
+   let flags = [
+      (attributes.bold, Attribute::Bold),
+      (attributes.dim, Attribute::Dim),
+      (attributes.italic, Attribute::Italic),
+      (attributes.underline, Attribute::Underlined),
+      (attributes.blink, Attribute::SlowBlink),
+      (attributes.reverse, Attribute::Reverse),
+      (attributes.strikethrough, Attribute::CrossedOut)
+   ];
+
+   flags.iter().filter(| flag | flag.0).map(| flag | flag.1).collect()
+}
 
-   move_cursor_right(message_x_offset).expect("Failed to set the cursor position. Exiting.");
+fn to_terminal_color(color: crate::color::Color) -> Color {
+   Color::Rgb { r: color.r as u8, g: color.g as u8, b: color.b as u8 }
 }
 
-pub fn reset_display_colors() -> Result<(), Error>{
-   let mut stdout = stdout();
+pub fn justify_line(line: &[Grapheme], box_width: usize, justify: Justify) -> (usize, &[Grapheme]) {
+   match justify {
+      Justify::Block => (0, line),
+      Justify::Left => {
+         let first_visible = line.iter().position(| grapheme | !grapheme.is_blank()).unwrap_or(line.len());
+
+         (0, &line[first_visible..])
+      },
+      Justify::Center => ((box_width - line_width(line)) / 2, line),
+      Justify::Right => {
+         let last_visible = line.iter().rposition(| grapheme | !grapheme.is_blank()).map_or(0, | index | index + 1);
+         let trimmed_line = &line[..last_visible];
 
-   match stdout.execute(ResetColor) {
-      Ok(_) => Ok(()),
-      Err(error) => Err(error)
+         (box_width - line_width(trimmed_line), trimmed_line)
+      }
    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,329 @@
+use std::{fs::File, io::{stdout, BufWriter, Error, Write}};
+
+use aligner::{color::Color, display::write_frame, parser::parse_program_colors, render::{Frame, FrameCell}};
+use clap::ValueEnum;
+
+use crate::arguments::ProgramArguments;
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+   Ansi,
+   Text,
+   Html,
+   Svg
+}
+
+pub struct ExportOptions {
+   pub format: ExportFormat,
+   pub font: String,
+   pub background: Color,
+   pub vector_blocks: bool
+}
+
+const SVG_CELL_WIDTH: f32 = 10.0;
+const SVG_CELL_HEIGHT: f32 = 20.0;
+const SVG_FONT_SIZE: f32 = 16.0;
+
+pub fn generate_export_options(arguments: &ProgramArguments, path: &str) -> Result<ExportOptions, String> {
+   let background = parse_program_colors(&vec![arguments.export_background.clone()])
+                       .map_err(| error | format!("Error while trying to parse the export background: {}", error))?[0];
+
+   Ok(ExportOptions {
+      format: arguments.export_format.unwrap_or(infer_export_format(path)),
+      font: arguments.export_font.clone(),
+      background,
+      vector_blocks: arguments.svg_blocks
+   })
+}
+
+pub fn export_frame(frame: &Frame, path: &str, options: &ExportOptions) -> Result<(), Error> {
+   if path == "-" {
+      return write_export(frame, &mut stdout(), options);
+   }
+
+   let mut file = BufWriter::new(File::create(path)?);
+
+   write_export(frame, &mut file, options)
+}
+
+fn infer_export_format(path: &str) -> ExportFormat {
+   let extension = path.rsplit_once('.').map(| (_, extension) | extension.to_lowercase());
+
+   match extension.as_deref() {
+      Some("html") | Some("htm") => ExportFormat::Html,
+      Some("svg") => ExportFormat::Svg,
+      Some("txt") => ExportFormat::Text,
+      _ => ExportFormat::Ansi
+   }
+}
+
+fn write_export(frame: &Frame, output: &mut impl Write, options: &ExportOptions) -> Result<(), Error> {
+   match options.format {
+      ExportFormat::Ansi => write_frame(frame, output, true),
+      ExportFormat::Text => write_frame(frame, output, false),
+      ExportFormat::Html => write_html(frame, output, options),
+      ExportFormat::Svg => write_svg(frame, output, options)
+   }
+}
+
+fn write_html(frame: &Frame, output: &mut impl Write, options: &ExportOptions) -> Result<(), Error> {
+   writeln!(output, "<!DOCTYPE html>")?;
+   writeln!(output, "<html>")?;
+   writeln!(output, "<head><meta charset=\"utf-8\"><title>Aligner</title></head>")?;
+   writeln!(output, "<body style=\"margin: 0; background: {};\">", hex_color(options.background))?;
+   write!(output, "<pre style=\"margin: 0; padding: 1em; font-family: {}; line-height: 1.2;\">", escape_html(&options.font))?;
+
+   for row in &frame.rows {
+      let end = row.iter().rposition(| cell | cell.as_ref().is_some_and(| cell | !cell.is_blank())).map_or(0, | index | index + 1);
+      let mut span_style = String::new();
+      let mut span_text = String::new();
+      let mut x = 0;
+
+      while x < end {
+         let (style, text, columns) = match &row[x] {
+            Some(cell) => (css_style(cell, options.background), cell.value.as_str(), cell.width()),
+            None => (String::new(), " ", 1)
+         };
+
+         if style != span_style {
+            write_span(output, &span_style, &span_text)?;
+            span_style = style;
+            span_text.clear();
+         }
+
+         span_text.push_str(text);
+         x += columns;
+      }
+
+      write_span(output, &span_style, &span_text)?;
+      writeln!(output)?;
+   }
+
+   writeln!(output, "</pre>")?;
+   writeln!(output, "</body>")?;
+   writeln!(output, "</html>")?;
+
+   output.flush()
+}
+
+fn write_span(output: &mut impl Write, style: &str, text: &str) -> Result<(), Error> {
+   if text.is_empty() {
+      return Ok(());
+   }
+
+   if style.is_empty() {
+      write!(output, "{}", escape_html(text))
+   } else {
+      write!(output, "<span style=\"{}\">{}</span>", style, escape_html(text))
+   }
+}
+
+fn css_style(cell: &FrameCell, page_background: Color) -> String {
+   if cell.is_blank() && !cell.attributes.reverse && !cell.attributes.underline && !cell.attributes.strikethrough {
+      return String::new();
+   }
+
+   let mut foreground = cell.foreground;
+   let mut background = cell.background;
+
+   if cell.attributes.reverse {
+      background = Some(foreground);
+      foreground = cell.background.unwrap_or(page_background);
+   }
+
+   let mut style = format!("color: {};", hex_color(foreground));
+
+   if let Some(background) = background {
+      style.push_str(&format!(" background: {};", hex_color(background)));
+   }
+
+   if cell.attributes.bold {
+      style.push_str(" font-weight: bold;");
+   }
+
+   if cell.attributes.dim {
+      style.push_str(" opacity: 0.6;");
+   }
+
+   if cell.attributes.italic {
+      style.push_str(" font-style: italic;");
+   }
+
+   let decorations: Vec<&str> = [(cell.attributes.underline, "underline"), (cell.attributes.strikethrough, "line-through")]
+                                   .iter()
+                                   .filter(| decoration | decoration.0)
+                                   .map(| decoration | decoration.1)
+                                   .collect();
+
+   if !decorations.is_empty() {
+      style.push_str(&format!(" text-decoration: {};", decorations.join(" ")));
+   }
+
+   style
+}
+
+fn write_svg(frame: &Frame, output: &mut impl Write, options: &ExportOptions) -> Result<(), Error> {
+   let (first_x, first_y, last_x, last_y) = visible_bounds(frame).unwrap_or((0, 0, frame.width, frame.height));
+   let left = first_x as f32 * SVG_CELL_WIDTH;
+   let top = first_y as f32 * SVG_CELL_HEIGHT;
+   let width = (last_x - first_x) as f32 * SVG_CELL_WIDTH;
+   let height = (last_y - first_y) as f32 * SVG_CELL_HEIGHT;
+
+   writeln!(output, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">", width, height, left, top, width, height)?;
+   writeln!(output, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", left, top, width, height, hex_color(options.background))?;
+   writeln!(output, "<g font-family=\"{}\" font-size=\"{}\" shape-rendering=\"crispEdges\">", escape_html(&options.font), SVG_FONT_SIZE)?;
+
+   for (y, row) in frame.rows.iter().enumerate() {
+      write_svg_backgrounds(row, y, output)?;
+
+      let mut x = 0;
+
+      while x < row.len() {
+         let cell = match &row[x] {
+            Some(cell) => cell,
+            None => {
+               x += 1;
+               continue;
+            }
+         };
+
+         let cell_x = x as f32 * SVG_CELL_WIDTH;
+         let cell_y = y as f32 * SVG_CELL_HEIGHT;
+
+         if options.vector_blocks && is_svg_block(cell) {
+            let run: Vec<Color> = row[x..].iter().map_while(| next | next.as_ref().filter(| next | is_svg_block(next)).map(| next | next.foreground)).collect();
+            let fill = if run.iter().all(| color | *color == cell.foreground) {
+               hex_color(cell.foreground)
+            } else {
+               write_svg_run_gradient(&run, x, y, output)?;
+               format!("url(#blocks-{}-{})", y, x)
+            };
+
+            writeln!(output, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", cell_x, cell_y, run.len() as f32 * SVG_CELL_WIDTH, SVG_CELL_HEIGHT, fill)?;
+
+            x += run.len();
+            continue;
+         }
+
+         let foreground = if cell.attributes.reverse { cell.background.unwrap_or(options.background) } else { cell.foreground };
+
+         if !cell.value.trim().is_empty() {
+            writeln!(output, "<text x=\"{}\" y=\"{}\" fill=\"{}\"{}>{}</text>", cell_x, cell_y + SVG_CELL_HEIGHT * 0.75, hex_color(foreground), svg_text_attributes(cell), escape_html(&cell.value))?;
+         }
+
+         x += cell.width();
+      }
+   }
+
+   writeln!(output, "</g>")?;
+   writeln!(output, "</svg>")?;
+
+   output.flush()
+}
+
+// Like the text exporters trim the blank cells around the message, the image only covers the
+// cells that show something, returned as the first and the end columns and rows.
+fn visible_bounds(frame: &Frame) -> Option<(usize, usize, usize, usize)> {
+   let mut bounds: Option<(usize, usize, usize, usize)> = None;
+
+   for (y, row) in frame.rows.iter().enumerate() {
+      for (x, cell) in row.iter().enumerate() {
+         let cell = match cell {
+            Some(cell) if !cell.is_blank() || cell.attributes.reverse => cell,
+            _ => continue
+         };
+
+         let end_x = x + cell.width();
+
+         bounds = Some(match bounds {
+            Some((first_x, first_y, last_x, _)) => (first_x.min(x), first_y, last_x.max(end_x), y + 1),
+            None => (x, y, end_x, y + 1)
+         });
+      }
+   }
+
+   bounds
+}
+
+// Backgrounds are drawn on their own, merging neighbour cells that share the same color, so
+// they stay in place whether the glyphs above them are merged or not.
+fn write_svg_backgrounds(row: &[Option<FrameCell>], y: usize, output: &mut impl Write) -> Result<(), Error> {
+   let mut run: Option<(usize, usize, Color)> = None;
+   let mut x = 0;
+
+   while x <= row.len() {
+      let (background, columns) = match row.get(x) {
+         Some(Some(cell)) => (if cell.attributes.reverse { Some(cell.foreground) } else { cell.background }, cell.width()),
+         _ => (None, 1)
+      };
+
+      match run {
+         Some((start, end, color)) if background == Some(color) && end == x => run = Some((start, x + columns, color)),
+         _ => {
+            if let Some((start, end, color)) = run {
+               writeln!(output, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", start as f32 * SVG_CELL_WIDTH, y as f32 * SVG_CELL_HEIGHT, (end - start) as f32 * SVG_CELL_WIDTH, SVG_CELL_HEIGHT, hex_color(color))?;
+            }
+
+            run = background.map(| color | (x, x + columns, color));
+         }
+      }
+
+      x += columns;
+   }
+
+   Ok(())
+}
+
+fn is_svg_block(cell: &FrameCell) -> bool {
+   cell.value == "█" && !cell.attributes.reverse
+}
+
+// Runs of blocks with different colors keep one rectangle, painted by a gradient with a hard
+// stop at every cell border.
+fn write_svg_run_gradient(colors: &[Color], x: usize, y: usize, output: &mut impl Write) -> Result<(), Error> {
+   writeln!(output, "<linearGradient id=\"blocks-{}-{}\">", y, x)?;
+
+   for (index, color) in colors.iter().enumerate() {
+      let start = index as f32 / colors.len() as f32;
+      let end = (index + 1) as f32 / colors.len() as f32;
+
+      writeln!(output, "<stop offset=\"{}\" stop-color=\"{}\"/><stop offset=\"{}\" stop-color=\"{}\"/>", start, hex_color(*color), end, hex_color(*color))?;
+   }
+
+   writeln!(output, "</linearGradient>")
+}
+
+fn svg_text_attributes(cell: &FrameCell) -> String {
+   let mut attributes = String::new();
+
+   if cell.attributes.bold {
+      attributes.push_str(" font-weight=\"bold\"");
+   }
+
+   if cell.attributes.italic {
+      attributes.push_str(" font-style=\"italic\"");
+   }
+
+   if cell.attributes.dim {
+      attributes.push_str(" opacity=\"0.6\"");
+   }
+
+   if cell.attributes.underline {
+      attributes.push_str(" text-decoration=\"underline\"");
+   } else if cell.attributes.strikethrough {
+      attributes.push_str(" text-decoration=\"line-through\"");
+   }
+
+   attributes
+}
+
+pub fn hex_color(color: Color) -> String {
+   format!("#{:02X}{:02X}{:02X}", color.r as u8, color.g as u8, color.b as u8)
+}
+
+pub fn escape_html(text: &str) -> String {
+   text.replace('&', "&amp;")
+       .replace('<', "&lt;")
+       .replace('>', "&gt;")
+       .replace('"', "&quot;")
+}
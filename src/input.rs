@@ -1,6 +1,8 @@
-use std::{f32::consts::PI, io::Error, ops::{AddAssign, SubAssign}, process::exit};
+use std::{f32::consts::PI, io::Error, ops::{AddAssign, SubAssign}};
 
-use crate::{leave_iterative_mode, parser::{parse_message, parse_program_colors, ControlMessageAction, ParsedControlMessage, ParsedMessage}, state::ProgramState, terminal::clear_terminal, typewriter::generate_message};
+use aligner::{parser::{parse_message, parse_program_colors, parse_text_attributes, ControlMessageAction, ParsedControlMessage, ParsedMessage}, state::ProgramState, typewriter::generate_message};
+
+use crate::terminal::clear_terminal;
 
 pub 
 fn handle_input(input: char, state: &mut ProgramState, keep_rendering: &mut bool, width: usize, height: usize) -> bool {
@@ -71,7 +73,7 @@ pub fn handle_network_input(input: ParsedControlMessage, state: &mut ProgramStat
          handle_network_set_color(input.value, state)?;
       },
       ControlMessageAction::SetMessage => {
-         handle_network_set_message(input.value, message);
+         handle_network_set_message(input.value, state, message)?;
       },
       ControlMessageAction::RotateLeft => {
          handle_network_rotation(state, true, message);
@@ -80,7 +82,10 @@ pub fn handle_network_input(input: ParsedControlMessage, state: &mut ProgramStat
          handle_network_rotation(state, false, message);
       },
       ControlMessageAction::SetText => {
-         handle_network_set_text(input.value, message);
+         handle_network_set_text(input.value, message)?;
+      },
+      ControlMessageAction::SetAttributes => {
+         handle_network_set_attributes(input.value, state)?;
       }
    }
 
@@ -120,24 +125,24 @@ fn handle_network_set_color(colors: String, state: &mut ProgramState) -> Result<
    }
 }
 
-fn handle_network_set_message(incoming_message: String, program_message: &mut ParsedMessage) {
-   clear_terminal().unwrap_or_else(| _ | {
-      leave_iterative_mode();
-      println!("Failed to clear the screen after the message was updated.");
-
-      exit(1);
-   });
+fn handle_network_set_attributes(attributes: String, state: &mut ProgramState) -> Result<(), String> {
+   state.attributes = parse_text_attributes(&attributes)?;
 
-   *program_message = parse_message(incoming_message);
+   Ok(())
 }
 
-fn handle_network_set_text(incoming_text: String, program_message: &mut ParsedMessage) {
-   clear_terminal().unwrap_or_else(|_| {
-      leave_iterative_mode();
-      println!("Failed to clear the screen after the message was updated.");
+fn handle_network_set_message(incoming_message: String, state: &ProgramState, program_message: &mut ParsedMessage) -> Result<(), String> {
+   clear_terminal().map_err(| _ | "Failed to clear the screen after the message was updated.".to_string())?;
+
+   *program_message = parse_message(incoming_message, state);
 
-      exit(1);
-   });
+   Ok(())
+}
+
+fn handle_network_set_text(incoming_text: String, program_message: &mut ParsedMessage) -> Result<(), String> {
+   clear_terminal().map_err(| _ | "Failed to clear the screen after the message was updated.".to_string())?;
 
    *program_message = generate_message(incoming_text);
+
+   Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,18 @@
+pub mod animation;
+pub mod color;
+pub mod display;
+pub mod parser;
+pub mod render;
+pub mod state;
+pub mod style;
+pub mod typewriter;
+
+mod ansi;
+mod decoration;
+mod random;
+mod transform;
+
+pub use parser::{parse_message, Grapheme, ParsedMessage};
+pub use render::{render, Frame, FrameCell, TerminalSize};
+pub use state::{ProgramState, ProgramStateBuilder};
+pub use typewriter::generate_message;
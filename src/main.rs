@@ -1,33 +1,33 @@
-use std::{process::exit, time::Duration};
-use arguments::ProgramArguments;
+use std::{io::{stdout, ErrorKind, IsTerminal}, process::exit, time::Duration};
+use aligner::{animation::Animation, display::{print_frame, write_frame}, parser::{parse_client_control_message, parse_message, ControlMessageAction, ParsedMessage}, render::{render_frame, RenderCache}, typewriter::generate_message, TerminalSize};
 use clap::Parser;
 use crossterm::event::{self, KeyCode};
-use display::{print_message, reset_display_colors};
+
+use arguments::{generate_program_state, ProgramArguments};
+use export::{export_frame, generate_export_options};
 use input::{handle_input, handle_network_input, read_message_file};
 use network::{send_error_message_to_client, ControlServer};
-use parser::{parse_client_control_message, parse_message};
-use state::generate_program_state;
-use terminal::{alternate_screen, change_cursor_visibility, clear_terminal, get_terminal_size, set_stdin_raw_mode, AlternateScreenAction, CursorVisibilityAction, SetStdinRawModeAction, TerminalSize};
-
-use crate::{parser::ParsedMessage, typewriter::generate_message};
+use recorder::Recorder;
+use terminal::{clear_terminal, enter_iterative_mode, get_terminal_size, leave_iterative_mode, print_empty_frame, print_message, reset_display_colors, supports_synchronized_updates, use_colors, SyncUpdates};
 
-mod terminal;
 mod arguments;
-mod color;
+mod export;
 mod input;
-mod display;
-mod parser;
-mod state;
 mod network;
-mod typewriter;
+mod recorder;
+mod terminal;
 
 fn main() {
    let mut keep_rendering: bool = true;
    let mut dimensions: TerminalSize;
-   let mut animate_draw = true;
    let arguments = ProgramArguments::parse();
    let mut parsed_message: ParsedMessage;
 
+   let mut state = generate_program_state(&arguments).unwrap_or_else(| error | {
+      println!("{}", error);
+      exit(1);
+   });
+
    if let Some(message_file) = &arguments.message_file  {
       let read_result = read_message_file(&message_file);
 
@@ -36,7 +36,7 @@ fn main() {
          exit(1);
       });    
 
-      parsed_message = parse_message(message);
+      parsed_message = parse_message(message, &state);
    } else if let Some(ref display_text) = arguments.display_text {
       parsed_message =  generate_message(display_text.clone());
    } else {
@@ -44,17 +44,38 @@ fn main() {
       exit(1);
    }
 
-   let mut state = generate_program_state(&arguments).unwrap_or_else(| error | {
-      println!("{}", error);
-      exit(1);
-   });
+   if let Some(output) = &arguments.output {
+      let TerminalSize { width, height } = fallback_dimensions(&arguments);
 
-   dimensions = get_terminal_size().unwrap_or_else(| _ | {
-      println!("Unable to retrieve terminal dimensions. Exiting.");
-      exit(1);
-   });
+      let export_options = generate_export_options(&arguments, output).unwrap_or_else(| error | {
+         println!("{}", error);
+         exit(1);
+      });
+
+      let frame = render_frame(&parsed_message, &state, width as usize, height as usize);
 
-   if arguments.disable_iterative == false {
+      export_frame(&frame, output, &export_options).unwrap_or_else(| error | {
+         println!("Unable to export the message to '{}'. Error: {}", output, error);
+         exit(1);
+      });
+
+      return;
+   }
+
+   let is_terminal = stdout().is_terminal();
+
+   if is_terminal {
+      dimensions = get_terminal_size().unwrap_or_else(| _ | {
+         println!("Unable to retrieve terminal dimensions. Exiting.");
+         exit(1);
+      });
+   } else {
+      dimensions = fallback_dimensions(&arguments);
+   }
+
+   let colored = use_colors(arguments.color, is_terminal);
+
+   if arguments.disable_iterative == false && is_terminal {
       let mut control_server: Option<ControlServer>;
 
       if let Some(address) = arguments.control_server {
@@ -68,7 +89,26 @@ fn main() {
          control_server = None;
       }
 
-      enter_iterative_mode();
+      let mut recorder = arguments.record.as_ref().map(| path | {
+         Recorder::start_recording(path, dimensions.width, dimensions.height).unwrap_or_else(| error | {
+            println!("Unable to start recording to '{}'. Error: {}", path, error);
+            exit(1);
+         })
+      });
+
+      enter_iterative_mode().unwrap_or_else(| error | {
+         println!("{}", error);
+         exit(1);
+      });
+
+      let synchronized = match arguments.sync_updates {
+         SyncUpdates::Auto => supports_synchronized_updates(),
+         SyncUpdates::Always => true,
+         SyncUpdates::Never => false
+      };
+
+      let mut animation = Animation::start(&state);
+      let mut render_cache = RenderCache::default();
 
       while keep_rendering {
          if let Some(ref mut server) = control_server {
@@ -77,7 +117,11 @@ fn main() {
             if let Some(read_result) = server.read_client_message() {
                match parse_client_control_message(read_result.0) {
                   Ok(parsed_incoming_message) => {
-                     handle_network_input(parsed_incoming_message, &mut state, &mut parsed_message).unwrap_or(());
+                     let replaces_message = matches!(parsed_incoming_message.action, ControlMessageAction::SetMessage | ControlMessageAction::SetText);
+
+                     if handle_network_input(parsed_incoming_message, &mut state, &mut parsed_message).is_ok() && replaces_message {
+                        animation.restart();
+                     }
                   },
                   Err(error) => {
                      send_error_message_to_client(error, read_result.1);
@@ -86,7 +130,12 @@ fn main() {
             }
          }
 
-         print_message(&parsed_message, &state, &dimensions, animate_draw, false);
+         print_message(&parsed_message, &state, &dimensions, &mut render_cache, &mut animation, synchronized, recorder.as_mut()).unwrap_or_else(| _ | {
+            leave_iterative_mode();
+            println!("Failed to draw the message. Exiting.");
+
+            exit(1);
+         });
 
          if event::poll(Duration::from_millis(20)).unwrap() {
             let read_result = event::read();
@@ -95,7 +144,9 @@ fn main() {
    
                if let event::Event::Key(key_event) = some_event {
    
-                  if let KeyCode::Char(c) = key_event.code {
+                  if animation.is_running() {
+                     animation.skip();
+                  } else if let KeyCode::Char(c) = key_event.code {
                      handle_input(c, &mut state, &mut keep_rendering, parsed_message.max_line_size, parsed_message.lines.len());
                   } else {
                      keep_rendering = false
@@ -104,63 +155,88 @@ fn main() {
                }
    
                if let event::Event::Resize(columns, rows) = some_event{
-                  dimensions.update(columns, rows);
-   
-                  clear_terminal().unwrap_or_else(| _ | {
-                     leave_iterative_mode();
-                     println!("Unable to clear the screen after terminal resize.");
-   
-                     exit(1);
-                  });
+                  handle_resize(columns, rows, &mut dimensions, recorder.as_mut());
                }
    
             }
          }
+      }
+
+      if let Some(outro) = &state.outro {
+         let mut outro = Animation::outro(outro, &state);
 
+         while outro.is_running() {
+            print_message(&parsed_message, &state, &dimensions, &mut render_cache, &mut outro, synchronized, recorder.as_mut()).unwrap_or(());
 
-         animate_draw = false;
+            if event::poll(Duration::from_millis(20)).unwrap_or(false) {
+               match event::read() {
+                  Ok(event::Event::Key(_)) => {
+                     outro.skip();
+                     print_empty_frame(&dimensions, synchronized, recorder.as_mut()).unwrap_or(());
+                  },
+                  Ok(event::Event::Resize(columns, rows)) => handle_resize(columns, rows, &mut dimensions, recorder.as_mut()),
+                  _ => {}
+               }
+            }
+         }
+      }
+
+      if let Some(ref mut recorder) = recorder {
+         recorder.finish();
       }
 
       leave_iterative_mode();
    }
 
    if arguments.disable_stdout == false {
-      print_message(&parsed_message, &state, &dimensions, false, true);
+      let frame = render_frame(&parsed_message, &state, dimensions.width as usize, dimensions.height as usize);
+
+      // Pipes and files get plain rows, while terminals keep the layout of the original printer.
+      let print_result = if is_terminal {
+         print_frame(&frame, &state, &mut stdout(), colored)
+      } else {
+         write_frame(&frame, &mut stdout(), colored)
+      };
+
+      print_result.unwrap_or_else(| error | {
+         if error.kind() != ErrorKind::BrokenPipe {
+            eprintln!("Failed to print the message. Exiting.");
+         }
+
+         exit(1);
+      });
    }
 
-   reset_display_colors().unwrap_or_else(| _ | {
-      println!("Couldn't reset the terminal colors");
-   });
+   if colored {
+      reset_display_colors().unwrap_or_else(| _ | {
+         println!("Couldn't reset the terminal colors");
+      });
+   }
 
 }
 
-fn enter_iterative_mode() {
-   alternate_screen(AlternateScreenAction::Enter).unwrap_or_else(| _ | {
-      println!("Couldn't enter in alternate screen. Exiting.");
+fn handle_resize(columns: u16, rows: u16, dimensions: &mut TerminalSize, recorder: Option<&mut Recorder>) {
+   dimensions.update(columns, rows);
 
-      exit(1);
-   });
+   if let Some(recorder) = recorder {
+      recorder.record_resize(columns, rows);
+   }
+
+   clear_terminal().unwrap_or_else(| _ | {
+      leave_iterative_mode();
+      println!("Unable to clear the screen after terminal resize.");
 
-   set_stdin_raw_mode(SetStdinRawModeAction::Enable).unwrap_or_else(| _ | {
-      alternate_screen(AlternateScreenAction::Leave).unwrap_or(());
-      println!("Couldn't set the stdin raw mode. Exiting.");
-      
       exit(1);
    });
-
-   change_cursor_visibility(CursorVisibilityAction::Hide).unwrap_or(());
 }
 
-fn leave_iterative_mode() {
-   alternate_screen(AlternateScreenAction::Leave).unwrap_or_else(| _ | {
-      println!("Couldn't leave the alternate screen mode.");
-   });
-
-   set_stdin_raw_mode(SetStdinRawModeAction::Disable).unwrap_or_else(| _ | {
-      print!("Couldn't disable the stdin raw mode.");
-   });
+// Used when the output is not a terminal: explicit sizes win, then the size of the
+// controlling terminal if there is one, and finally a classic 80x24 screen.
+fn fallback_dimensions(arguments: &ProgramArguments) -> TerminalSize {
+   let terminal_size = get_terminal_size().unwrap_or(TerminalSize { width: 80, height: 24 });
 
-   change_cursor_visibility(CursorVisibilityAction::Show).unwrap_or_else(| _ | {
-      println!("Couldn't change the cursor visibility to visibile.");
-   });
-}
\ No newline at end of file
+   TerminalSize {
+      width: arguments.width.unwrap_or(terminal_size.width),
+      height: arguments.height.unwrap_or(terminal_size.height)
+   }
+}
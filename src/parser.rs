@@ -1,7 +1,8 @@
 use serde::Deserialize;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use crate::color::Color;
+use crate::{ansi::{split_escape_sequences, StyledSegment}, color::Color, state::{AnsiHandling, ControlCharacters, ProgramState}, style::{Attribute, GraphemeStyle, TextAttributes}};
 
 pub enum ControlMessageAction {
    RotateLeft,
@@ -9,12 +10,42 @@ pub enum ControlMessageAction {
    SetMessage,
    SetColor,
    SetAngle,
-   SetText
+   SetText,
+   SetAttributes
 }
 
+#[derive(PartialEq, Clone)]
+pub struct Grapheme {
+   pub value: String,
+   pub style: Option<GraphemeStyle>,
+   pub glyph: Option<usize>
+}
+
+impl Grapheme {
+   pub fn blank() -> Grapheme {
+      Grapheme { value: " ".to_string(), style: None, glyph: None }
+   }
+
+   pub fn is_blank(&self) -> bool {
+      self.value.trim().is_empty() && self.style.is_none_or(| style | style.background.is_none())
+   }
+}
+
+#[derive(PartialEq, Clone)]
 pub struct ParsedMessage {
    pub max_line_size: usize,
-   pub lines: Vec<Vec<String>>
+   pub lines: Vec<Vec<Grapheme>>
+}
+
+enum MarkupTag {
+   Foreground(Color),
+   Background(Color),
+   Attribute(Attribute)
+}
+
+enum MarkupAction {
+   Open(MarkupTag),
+   Close(usize)
 }
 
 pub struct ParsedControlMessage {
@@ -28,29 +59,195 @@ struct ControlMessage {
    message: Option<String>,
    colors: Option<String>,
    angle: Option<u16>,
+   attributes: Option<String>,
 
 }
 
-pub fn parse_message(message: String) -> ParsedMessage {
+pub fn parse_message(message: String, state: &ProgramState) -> ParsedMessage {
    let mut file_lines = Vec::new();
    let mut max_line_size = 0;
+   let mut current_style = GraphemeStyle::default();
+   let mut open_tags: Vec<MarkupTag> = Vec::new();
+
+   for line in message.replace("\r\n", "\n").split("\n") {
+      // Stripped escape sequences are left to the control characters policy, so they can still be
+      // made visible instead of being silently interpreted.
+      let mut segments = if state.ansi == AnsiHandling::Strip && state.control_characters == ControlCharacters::Escape {
+         vec![StyledSegment { text: line.to_string(), style: None }]
+      } else {
+         split_escape_sequences(line, &mut current_style)
+      };
+
+      for segment in segments.iter_mut() {
+         segment.style = match state.ansi {
+            AnsiHandling::Keep => segment.style,
+            AnsiHandling::Strip => None,
+            AnsiHandling::Override => segment.style.map(| style | style.without_colors()).filter(| style | !style.is_empty())
+         };
+      }
 
-   for line in message.split("\n") {
-      let graphemes: Vec<&str> = line.graphemes(true).collect();
-
-      if graphemes.len() > max_line_size {
-         max_line_size = graphemes.len();
+      if state.markup {
+         segments = parse_markup(segments, &mut open_tags);
       }
 
+      let graphemes = sanitize_line(&segments, state.tab_width, state.control_characters);
+
+      max_line_size = max_line_size.max(line_width(&graphemes));
+
       file_lines.push(graphemes);
    }
 
    ParsedMessage {
-      lines: file_lines.iter().map(|v| v.iter().map(|s| s.to_string()).collect()).collect(),
-      max_line_size: max_line_size
+      lines: file_lines,
+      max_line_size
    }
 }
 
+fn sanitize_line(segments: &[StyledSegment], tab_width: usize, policy: ControlCharacters) -> Vec<Grapheme> {
+   let mut graphemes: Vec<Grapheme> = Vec::new();
+   let mut column = 0;
+
+   for segment in segments {
+      for grapheme in segment.text.graphemes(true) {
+         if grapheme == "\t" {
+            let spaces = if tab_width > 0 { tab_width - column % tab_width } else { 0 };
+
+            graphemes.extend(std::iter::repeat_n(Grapheme { value: " ".to_string(), style: segment.style, glyph: None }, spaces));
+            column += spaces;
+         } else if grapheme.chars().any(char::is_control) {
+            if policy == ControlCharacters::Escape {
+               let escaped = escape_control_characters(grapheme);
+
+               column += grapheme_width(&escaped);
+               graphemes.push(Grapheme { value: escaped, style: segment.style, glyph: None });
+            }
+         } else {
+            column += grapheme_width(grapheme);
+            graphemes.push(Grapheme { value: grapheme.to_string(), style: segment.style, glyph: None });
+         }
+      }
+   }
+
+   graphemes
+}
+
+fn parse_markup(segments: Vec<StyledSegment>, open_tags: &mut Vec<MarkupTag>) -> Vec<StyledSegment> {
+   let mut styled_segments: Vec<StyledSegment> = Vec::new();
+
+   for segment in segments {
+      let mut text = String::new();
+      let mut rest = segment.text.as_str();
+
+      while let Some(start) = rest.find('{') {
+         text.push_str(&rest[..start]);
+         rest = &rest[start..];
+
+         if rest.starts_with("{{") {
+            text.push('{');
+            rest = &rest[2..];
+            continue;
+         }
+
+         let action = rest.find('}').and_then(| end | Some((end, parse_markup_tag(&rest[1..end], open_tags)?)));
+
+         match action {
+            Some((end, action)) => {
+               if !text.is_empty() {
+                  styled_segments.push(StyledSegment { text: std::mem::take(&mut text), style: markup_style(segment.style, open_tags) });
+               }
+
+               match action {
+                  MarkupAction::Open(tag) => open_tags.push(tag),
+                  MarkupAction::Close(position) => {
+                     open_tags.remove(position);
+                  }
+               }
+
+               rest = &rest[end + 1..];
+            },
+            None => {
+               text.push('{');
+               rest = &rest[1..];
+            }
+         }
+      }
+
+      text.push_str(rest);
+
+      if !text.is_empty() {
+         styled_segments.push(StyledSegment { text, style: markup_style(segment.style, open_tags) });
+      }
+   }
+
+   styled_segments
+}
+
+fn parse_markup_tag(tag: &str, open_tags: &[MarkupTag]) -> Option<MarkupAction> {
+   if let Some(name) = tag.strip_prefix('/') {
+      let position = open_tags.iter().rposition(| open_tag | match (open_tag, name) {
+         (MarkupTag::Foreground(_), "fg") | (MarkupTag::Background(_), "bg") => true,
+         (MarkupTag::Attribute(attribute), name) => markup_attribute(name) == Some(*attribute),
+         _ => false
+      });
+
+      return position.map(MarkupAction::Close);
+   }
+
+   let tag = match tag.split_once('=') {
+      Some(("fg", color)) => MarkupTag::Foreground(parse_hex_color(color)?),
+      Some(("bg", color)) => MarkupTag::Background(parse_hex_color(color)?),
+      Some(_) => return None,
+      None => MarkupTag::Attribute(markup_attribute(tag)?)
+   };
+
+   Some(MarkupAction::Open(tag))
+}
+
+fn markup_attribute(name: &str) -> Option<Attribute> {
+   match name {
+      "b" => Some(Attribute::Bold),
+      "d" => Some(Attribute::Dim),
+      "i" => Some(Attribute::Italic),
+      "u" => Some(Attribute::Underline),
+      "r" => Some(Attribute::Reverse),
+      "s" | "strike" => Some(Attribute::Strikethrough),
+      _ => Attribute::from_name(name)
+   }
+}
+
+fn markup_style(base: Option<GraphemeStyle>, open_tags: &[MarkupTag]) -> Option<GraphemeStyle> {
+   let mut style = base.unwrap_or_default();
+
+   for tag in open_tags {
+      match tag {
+         MarkupTag::Foreground(color) => style.foreground = Some(*color),
+         MarkupTag::Background(color) => style.background = Some(*color),
+         MarkupTag::Attribute(attribute) => style.attributes.set(*attribute, true)
+      }
+   }
+
+   if style.is_empty() { None } else { Some(style) }
+}
+
+fn escape_control_characters(grapheme: &str) -> String {
+   grapheme.chars().map(| c | {
+      match c as u32 {
+         0x00..=0x1F => format!("^{}", (c as u8 + 0x40) as char),
+         0x7F => "^?".to_string(),
+         code if c.is_control() => format!("\\x{:02X}", code),
+         _ => c.to_string()
+      }
+   }).collect()
+}
+
+pub fn grapheme_width(grapheme: &str) -> usize {
+   grapheme.width()
+}
+
+pub fn line_width(line: &[Grapheme]) -> usize {
+   line.iter().map(|grapheme| grapheme_width(&grapheme.value)).sum()
+}
+
 pub fn parse_program_colors(colors: &Vec<String>) -> Result<Vec<Color>, String> {
    let mut parsed_colors = Vec::new();
 
@@ -72,11 +269,28 @@ pub fn parse_program_colors(colors: &Vec<String>) -> Result<Vec<Color>, String>
    Ok(parsed_colors)
 }
 
+pub fn parse_text_attributes(attributes: &str) -> Result<TextAttributes, String> {
+   let mut parsed_attributes = TextAttributes::default();
+
+   for attribute in attributes.split_whitespace() {
+      match Attribute::from_name(attribute) {
+         Some(attribute) => parsed_attributes.set(attribute, true),
+         None => return Err(format!("\"{}\" is not a valid text attribute.", attribute))
+      }
+   }
+
+   Ok(parsed_attributes)
+}
+
 fn parse_hex_color(s: &str) -> Option<Color> {
+   if !s.starts_with('#') || s.len() != 7 {
+      return None;
+   }
+
    Some(Color {
-      r: u8::from_str_radix(&s[1..3], 16).ok()? as f32,
-      g: u8::from_str_radix(&s[3..5], 16).ok()? as f32,
-      b: u8::from_str_radix(&s[5..7], 16).ok()? as f32
+      r: u8::from_str_radix(s.get(1..3)?, 16).ok()? as f32,
+      g: u8::from_str_radix(s.get(3..5)?, 16).ok()? as f32,
+      b: u8::from_str_radix(s.get(5..7)?, 16).ok()? as f32
    })
 }
 
@@ -144,6 +358,17 @@ pub fn parse_client_control_message(message: String) -> Result<ParsedControlMess
                return Ok(return_value);
             }
 
+         },
+         "SET_ATTRIBUTES" => {
+            if let Some(attributes) = parsed_message.attributes {
+
+               let return_value = ParsedControlMessage {
+                  action: ControlMessageAction::SetAttributes,
+                  value: attributes
+               };
+
+               return Ok(return_value);
+            }
          }
          _ => {
             return Err("The sent message is not a valid JSON for this application.".to_string())
@@ -154,4 +379,123 @@ pub fn parse_client_control_message(message: String) -> Result<ParsedControlMess
    }
 
    Err("The sent message is not a valid JSON for this application.".to_string())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn parse(message: &str, state: &ProgramState) -> ParsedMessage {
+      parse_message(message.to_string(), state)
+   }
+
+   fn line_text(message: &ParsedMessage, line: usize) -> String {
+      message.lines[line].iter().map(| grapheme | grapheme.value.as_str()).collect()
+   }
+
+   #[test]
+   fn expands_tabs_to_the_next_stop() {
+      let state = ProgramState::builder().tab_width(4).build();
+      let message = parse("a\tb\tc", &state);
+
+      assert_eq!(line_text(&message, 0), "a   b   c");
+   }
+
+   #[test]
+   fn counts_wide_glyphs_before_tab_stops() {
+      let state = ProgramState::builder().tab_width(4).build();
+      let message = parse("漢\tx", &state);
+
+      assert_eq!(line_text(&message, 0), "漢  x");
+      assert_eq!(message.max_line_size, 5);
+   }
+
+   #[test]
+   fn normalizes_crlf_line_endings() {
+      let state = ProgramState::builder().build();
+      let message = parse("ab\r\ncd\r\n", &state);
+
+      assert_eq!(message.lines.len(), 3);
+      assert_eq!(line_text(&message, 0), "ab");
+      assert_eq!(line_text(&message, 1), "cd");
+      assert!(message.lines[2].is_empty());
+   }
+
+   #[test]
+   fn strips_control_characters_by_default() {
+      let state = ProgramState::builder().build();
+      let message = parse("a\x07b\rc", &state);
+
+      assert_eq!(line_text(&message, 0), "abc");
+   }
+
+   #[test]
+   fn escapes_control_characters_in_caret_notation() {
+      let state = ProgramState::builder().control_characters(ControlCharacters::Escape).build();
+      let message = parse("a\x07b\x7f", &state);
+
+      assert_eq!(line_text(&message, 0), "a^Gb^?");
+      assert_eq!(message.max_line_size, 6);
+   }
+
+   #[test]
+   fn escapes_stripped_escape_sequences() {
+      let state = ProgramState::builder().control_characters(ControlCharacters::Escape).ansi(AnsiHandling::Strip).build();
+      let message = parse("\x1b[31mred", &state);
+
+      assert_eq!(line_text(&message, 0), "^[[31mred");
+   }
+
+   fn style_at(message: &ParsedMessage, line: usize, column: usize) -> GraphemeStyle {
+      message.lines[line][column].style.unwrap_or_default()
+   }
+
+   #[test]
+   fn applies_nested_markup_tags() {
+      let state = ProgramState::builder().markup(true).build();
+      let message = parse("{b}a{fg=#FF0000}b{/fg}c{/b}d", &state);
+
+      assert_eq!(line_text(&message, 0), "abcd");
+      assert!(style_at(&message, 0, 0).attributes.bold);
+      assert_eq!(style_at(&message, 0, 1).foreground, Some(Color { r: 255.0, g: 0.0, b: 0.0 }));
+      assert!(style_at(&message, 0, 1).attributes.bold);
+      assert!(style_at(&message, 0, 2).foreground.is_none());
+      assert!(message.lines[0][3].style.is_none());
+   }
+
+   #[test]
+   fn closes_the_innermost_matching_tag() {
+      let state = ProgramState::builder().markup(true).build();
+      let message = parse("{u}{b}a{/u}b{/b}", &state);
+
+      assert!(style_at(&message, 0, 0).attributes.underline);
+      assert!(!style_at(&message, 0, 1).attributes.underline);
+      assert!(style_at(&message, 0, 1).attributes.bold);
+   }
+
+   #[test]
+   fn keeps_unclosed_tags_open_across_lines() {
+      let state = ProgramState::builder().markup(true).build();
+      let message = parse("{i}a\nb{/i}c", &state);
+
+      assert!(style_at(&message, 0, 0).attributes.italic);
+      assert!(style_at(&message, 1, 0).attributes.italic);
+      assert!(message.lines[1][1].style.is_none());
+   }
+
+   #[test]
+   fn keeps_unknown_and_unmatched_tags_as_text() {
+      let state = ProgramState::builder().markup(true).build();
+      let message = parse("{x}a{/b}{fg=red}{{b}", &state);
+
+      assert_eq!(line_text(&message, 0), "{x}a{/b}{fg=red}{b}");
+      assert!(message.lines[0].iter().all(| grapheme | grapheme.style.is_none()));
+   }
+
+   #[test]
+   fn ignores_markup_when_disabled() {
+      let state = ProgramState::builder().build();
+      let message = parse("{b}a{/b}", &state);
+
+      assert_eq!(line_text(&message, 0), "{b}a{/b}");
+   }
+}
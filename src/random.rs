@@ -0,0 +1,44 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A small splitmix64 generator. It is not meant for anything sensitive, only to make the
+// random animations reproducible when the same seed is given.
+pub struct Random {
+   state: u64
+}
+
+impl Random {
+   pub fn new(seed: u64) -> Random {
+      Random { state: seed }
+   }
+
+   pub fn next_u64(&mut self) -> u64 {
+      self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+
+      let mut value = self.state;
+
+      value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+      value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+      value ^ (value >> 31)
+   }
+
+   pub fn next_below(&mut self, bound: usize) -> usize {
+      if bound == 0 {
+         return 0;
+      }
+
+      (self.next_u64() % bound as u64) as usize
+   }
+
+   pub fn shuffle<T>(&mut self, items: &mut [T]) {
+      for index in (1..items.len()).rev() {
+         let other = self.next_below(index + 1);
+
+         items.swap(index, other);
+      }
+   }
+}
+
+pub fn generate_seed() -> u64 {
+   SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, | duration | duration.as_nanos() as u64)
+}
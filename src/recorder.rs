@@ -0,0 +1,70 @@
+use std::{fs::File, io::{BufWriter, Error, Write}, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use serde::Serialize;
+
+pub struct Recorder {
+   file: BufWriter<File>,
+   start: Instant,
+   last_output: Vec<u8>
+}
+
+#[derive(Serialize)]
+struct CastHeader {
+   version: u8,
+   width: u16,
+   height: u16,
+   timestamp: u64,
+   env: CastEnvironment
+}
+
+#[derive(Serialize)]
+struct CastEnvironment {
+   #[serde(rename = "TERM")]
+   term: Option<String>,
+   #[serde(rename = "SHELL")]
+   shell: Option<String>
+}
+
+impl Recorder {
+   pub fn start_recording(path: &str, width: u16, height: u16) -> Result<Recorder, Error> {
+      let mut file = BufWriter::new(File::create(path)?);
+
+      let header = CastHeader {
+         version: 2,
+         width,
+         height,
+         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, | duration | duration.as_secs()),
+         env: CastEnvironment { term: std::env::var("TERM").ok(), shell: std::env::var("SHELL").ok() }
+      };
+
+      writeln!(file, "{}", serde_json::to_string(&header)?)?;
+
+      Ok(Recorder { file, start: Instant::now(), last_output: Vec::new() })
+   }
+
+   pub fn record_output(&mut self, data: &[u8]) {
+      if data.is_empty() || data == self.last_output.as_slice() {
+         return;
+      }
+
+      self.write_event("o", String::from_utf8_lossy(data).to_string());
+      self.last_output = data.to_vec();
+   }
+
+   pub fn record_resize(&mut self, width: u16, height: u16) {
+      self.write_event("r", format!("{}x{}", width, height));
+      self.last_output.clear();
+   }
+
+   fn write_event(&mut self, code: &str, data: String) {
+      let elapsed = self.start.elapsed().as_secs_f64();
+
+      if let Ok(event) = serde_json::to_string(&(elapsed, code, data)) {
+         writeln!(self.file, "{}", event).unwrap_or(());
+      }
+   }
+
+   pub fn finish(&mut self) {
+      self.file.flush().unwrap_or(());
+   }
+}
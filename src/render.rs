@@ -0,0 +1,186 @@
+use std::io::{Error, Write};
+
+use crate::{color::{interpolate_multi_color, Color}, decoration::decorate_message, display::{justify_line, write_frame}, parser::{grapheme_width, ParsedMessage}, state::{Justify, ProgramState}, style::TextAttributes};
+
+#[derive(Clone)]
+pub struct FrameCell {
+   pub value: String,
+   pub foreground: Color,
+   pub background: Option<Color>,
+   pub attributes: TextAttributes,
+   pub glyph: Option<usize>
+}
+
+#[derive(Clone)]
+pub struct Frame {
+   pub width: usize,
+   pub height: usize,
+   pub rows: Vec<Vec<Option<FrameCell>>>
+}
+
+#[derive(Clone, Copy)]
+pub struct TerminalSize {
+   pub width: u16,
+   pub height: u16
+}
+
+impl TerminalSize {
+   pub fn update(&mut self, width: u16, height: u16) {
+      self.width = width;
+      self.height = height;
+   }
+}
+
+impl FrameCell {
+   pub fn width(&self) -> usize {
+      grapheme_width(&self.value).max(1)
+   }
+
+   pub fn is_blank(&self) -> bool {
+      self.value.trim().is_empty() && self.background.is_none()
+   }
+}
+
+impl Frame {
+   pub fn write_to(&self, mut output: impl Write) -> Result<(), Error> {
+      write_frame(self, &mut output, true)
+   }
+}
+
+pub fn render(message: &ParsedMessage, state: &ProgramState, size: TerminalSize) -> Frame {
+   render_frame(message, state, size.width as usize, size.height as usize)
+}
+
+// Keeps the last rendered frame together with everything it was rendered from, so redraws of an
+// unchanged message only clone it. Any change to the message, the state or the size is noticed
+// by comparing them, and renders a new frame.
+#[derive(Default)]
+pub struct RenderCache {
+   entry: Option<RenderCacheEntry>
+}
+
+struct RenderCacheEntry {
+   message: ParsedMessage,
+   state: ProgramState,
+   width: usize,
+   height: usize,
+   frame: Frame
+}
+
+impl RenderCache {
+   pub fn render(&mut self, message: &ParsedMessage, state: &ProgramState, size: TerminalSize) -> Frame {
+      let (width, height) = (size.width as usize, size.height as usize);
+
+      if let Some(entry) = &self.entry {
+         if entry.width == width && entry.height == height && entry.state == *state && entry.message == *message {
+            return entry.frame.clone();
+         }
+      }
+
+      let frame = render_frame(message, state, width, height);
+
+      self.entry = Some(RenderCacheEntry { message: message.clone(), state: state.clone(), width, height, frame: frame.clone() });
+
+      frame
+   }
+}
+
+pub fn render_frame(message: &ParsedMessage, state: &ProgramState, width: usize, height: usize) -> Frame {
+   let decorated = decorate_message(message, state);
+
+   let (message, justify) = match &decorated {
+      Some(decorated) => (decorated, Justify::Block),
+      None => (message, state.justify)
+   };
+
+   let message_height = message.lines.len();
+
+   let frame_height = if state.align_vertically { height } else { message_height };
+   let mut rows: Vec<Vec<Option<FrameCell>>> = vec![vec![None; width]; frame_height];
+
+   let x_offset = if state.align_horizontally {
+      (width / 2).saturating_sub(message.max_line_size / 2)
+   } else {
+      0
+   };
+
+   let y_offset = if state.align_vertically {
+      (height / 2).saturating_sub(message_height / 2)
+   } else {
+      0
+   };
+
+   for (line_number, line) in message.lines.iter().enumerate() {
+      let y = y_offset + line_number;
+
+      if y >= frame_height {
+         break;
+      }
+
+      let (mut column, justified_line) = justify_line(line, message.max_line_size, justify);
+
+      for grapheme in justified_line {
+         let grapheme_columns = grapheme_width(&grapheme.value);
+         let x = x_offset + column;
+
+         if x + grapheme_columns > width {
+            break;
+         }
+
+         let style = grapheme.style.unwrap_or_default();
+
+         let foreground = match style.foreground {
+            Some(foreground) => foreground,
+            None => interpolate_multi_color(column as f32, line_number as f32, message.max_line_size as f32, message_height as f32, state.angle, &state.colors)
+         };
+
+         if grapheme_columns > 0 {
+            rows[y][x] = Some(FrameCell {
+               value: grapheme.value.clone(),
+               foreground,
+               background: style.background,
+               attributes: style.attributes.combine(&state.attributes),
+               glyph: grapheme.glyph
+            });
+         }
+
+         column += grapheme_columns;
+      }
+   }
+
+   Frame { width, height: frame_height, rows }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{parser::parse_message, state::ProgramState};
+
+   #[test]
+   fn cache_renders_again_when_the_state_changes() {
+      let mut state = ProgramState::builder().build();
+      let message = parse_message("ab".to_string(), &state);
+      let size = TerminalSize { width: 4, height: 1 };
+      let mut cache = RenderCache::default();
+
+      let first = cache.render(&message, &state, size);
+      state.colors = vec![Color { r: 255.0, g: 0.0, b: 0.0 }];
+      let second = cache.render(&message, &state, size);
+
+      assert_eq!(first.rows[0][0].as_ref().unwrap().foreground, Color { r: 255.0, g: 255.0, b: 255.0 });
+      assert_eq!(second.rows[0][0].as_ref().unwrap().foreground, Color { r: 255.0, g: 0.0, b: 0.0 });
+   }
+
+   #[test]
+   fn cache_renders_again_when_the_size_changes() {
+      let state = ProgramState::builder().align_horizontally(true).build();
+      let message = parse_message("ab".to_string(), &state);
+      let mut cache = RenderCache::default();
+
+      cache.render(&message, &state, TerminalSize { width: 4, height: 1 });
+      let frame = cache.render(&message, &state, TerminalSize { width: 10, height: 1 });
+
+      assert_eq!(frame.width, 10);
+      assert!(frame.rows[0][4].is_some());
+   }
+}
@@ -1,66 +1,326 @@
-use std::process::exit;
+use clap::ValueEnum;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{arguments::ProgramArguments, color::Color, parser::parse_program_colors};
+use crate::{color::Color, random::generate_seed, style::TextAttributes};
 
-#[derive(PartialEq)]
+const DEFAULT_DECODE_CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789@#$%&*+=<>?/\\|";
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum ProgramMode {
    CharByChar,
    LineByLine,
+   WordByWord,
+   ColumnByColumn,
+   Fade,
+   Decode,
+   Wipe,
+   Slide,
+   Dissolve,
    Flash
 }
 
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum Justify {
+   Block,
+   Left,
+   Center,
+   Right
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum ControlCharacters {
+   Strip,
+   Escape
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum AnsiHandling {
+   Keep,
+   Strip,
+   Override
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum BorderStyle {
+   Single,
+   Double,
+   Rounded,
+   Heavy,
+   Ascii,
+   Custom
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum ShadowStyle {
+   Drop,
+   Extrude
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum Direction {
+   Up,
+   Down,
+   Left,
+   Right,
+   UpLeft,
+   UpRight,
+   DownLeft,
+   DownRight
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum Transform {
+   FlipH,
+   FlipV,
+   Rotate90,
+   Rotate180,
+   Rotate270
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum Easing {
+   Linear,
+   EaseIn,
+   EaseOut,
+   EaseInOut
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum OutroStyle {
+   Reverse,
+   Fade,
+   Dissolve,
+   Wipe
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum RevealOrder {
+   LeftToRight,
+   Random
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Outro {
+   pub style: OutroStyle,
+   pub duration: Option<u64>
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Shadow {
+   pub x_step: isize,
+   pub y_step: isize,
+   pub layers: usize,
+   pub color: Color
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Outline {
+   pub character: String,
+   pub color: Option<Color>,
+   pub hollow: bool
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Border {
+   pub characters: Vec<String>,
+   pub vertical_padding: usize,
+   pub horizontal_padding: usize,
+   pub title: Option<String>,
+   pub colors: Option<Vec<Color>>
+}
+
+#[derive(PartialEq, Clone)]
 pub struct ProgramState {
    pub angle: f32,
    pub colors: Vec<Color>,
    pub mode: ProgramMode,
    pub animation_delay: u64,
    pub align_vertically: bool,
-   pub align_horizontally: bool
+   pub align_horizontally: bool,
+   pub justify: Justify,
+   pub tab_width: usize,
+   pub control_characters: ControlCharacters,
+   pub ansi: AnsiHandling,
+   pub markup: bool,
+   pub attributes: TextAttributes,
+   pub border: Option<Border>,
+   pub shadow: Option<Shadow>,
+   pub outline: Option<Outline>,
+   pub transform: Option<Transform>,
+   pub outro: Option<Outro>,
+   pub reveal_order: RevealOrder,
+   pub decode_characters: Vec<String>,
+   pub seed: u64,
+   pub easing: Easing,
+   pub duration: Option<u64>
+}
+
+pub struct ProgramStateBuilder {
+   state: ProgramState,
+   justify: Option<Justify>
 }
 
 impl ProgramState {
-   pub fn new(angle: i16, colors: Vec<Color>, mode: ProgramMode, animation_delay: u64, align_vertically: bool, align_horizontally: bool) -> ProgramState {
-      return ProgramState { angle: angle as f32, colors, mode, animation_delay, align_horizontally, align_vertically };
+   pub fn builder() -> ProgramStateBuilder {
+      ProgramStateBuilder::new()
    }
 }
 
-pub fn generate_program_state(arguments: &ProgramArguments) -> Result<ProgramState, String> {
-   let colors = match &arguments.colors {
-      Some(colors) => {
-         let parsed_colors = parse_program_colors(colors);
+impl Default for ProgramStateBuilder {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl ProgramStateBuilder {
+   pub fn new() -> ProgramStateBuilder {
+      let state = ProgramState {
+         angle: 0.0,
+         colors: vec![Color { r: 255.0, g: 255.0, b: 255.0 }],
+         mode: ProgramMode::Flash,
+         animation_delay: 0,
+         align_vertically: false,
+         align_horizontally: false,
+         justify: Justify::Block,
+         tab_width: 8,
+         control_characters: ControlCharacters::Strip,
+         ansi: AnsiHandling::Keep,
+         markup: false,
+         attributes: TextAttributes::default(),
+         border: None,
+         shadow: None,
+         outline: None,
+         transform: None,
+         outro: None,
+         reveal_order: RevealOrder::LeftToRight,
+         decode_characters: split_characters(DEFAULT_DECODE_CHARACTERS),
+         seed: generate_seed(),
+         easing: Easing::Linear,
+         duration: None
+      };
+
+      ProgramStateBuilder { state, justify: None }
+   }
+
+   pub fn angle(mut self, angle: f32) -> Self {
+      self.state.angle = angle;
+      self
+   }
+
+   pub fn colors(mut self, colors: Vec<Color>) -> Self {
+      self.state.colors = colors;
+      self
+   }
+
+   pub fn mode(mut self, mode: ProgramMode, animation_delay: u64) -> Self {
+      self.state.mode = mode;
+      self.state.animation_delay = animation_delay;
+      self
+   }
+
+   pub fn align_vertically(mut self, align: bool) -> Self {
+      self.state.align_vertically = align;
+      self
+   }
+
+   pub fn align_horizontally(mut self, align: bool) -> Self {
+      self.state.align_horizontally = align;
+      self
+   }
+
+   pub fn justify(mut self, justify: Justify) -> Self {
+      self.justify = Some(justify);
+      self
+   }
+
+   pub fn tab_width(mut self, tab_width: usize) -> Self {
+      self.state.tab_width = tab_width;
+      self
+   }
+
+   pub fn control_characters(mut self, control_characters: ControlCharacters) -> Self {
+      self.state.control_characters = control_characters;
+      self
+   }
+
+   pub fn ansi(mut self, ansi: AnsiHandling) -> Self {
+      self.state.ansi = ansi;
+      self
+   }
+
+   pub fn markup(mut self, markup: bool) -> Self {
+      self.state.markup = markup;
+      self
+   }
+
+   pub fn attributes(mut self, attributes: TextAttributes) -> Self {
+      self.state.attributes = attributes;
+      self
+   }
+
+   pub fn border(mut self, border: Border) -> Self {
+      self.state.border = Some(border);
+      self
+   }
+
+   pub fn shadow(mut self, shadow: Shadow) -> Self {
+      self.state.shadow = Some(shadow);
+      self
+   }
+
+   pub fn outline(mut self, outline: Outline) -> Self {
+      self.state.outline = Some(outline);
+      self
+   }
 
-         let parsed_colors = parsed_colors.unwrap_or_else(| error | {
-            println!("Error while trying to parse the colors argument: {}", error);
-            exit(1);
-         });
+   pub fn transform(mut self, transform: Transform) -> Self {
+      self.state.transform = Some(transform);
+      self
+   }
 
-         parsed_colors
-      },
-      None => {
-         vec![Color { r: 255.0, g: 255.0, b: 255.0 }]
-      }
-   };
+   pub fn outro(mut self, outro: Outro) -> Self {
+      self.state.outro = Some(outro);
+      self
+   }
 
-   let mut mode = ProgramMode::Flash;
-   let mut delay = 0;
+   pub fn reveal_order(mut self, reveal_order: RevealOrder) -> Self {
+      self.state.reveal_order = reveal_order;
+      self
+   }
 
-   if let Some(line_by_line_delay) = arguments.line_by_line_mode {
-      mode =  ProgramMode::LineByLine;
-      delay = line_by_line_delay;
+   pub fn decode_characters(mut self, characters: Vec<String>) -> Self {
+      self.state.decode_characters = characters;
+      self
    }
 
-   if let Some(char_by_char_delay) = arguments.char_by_char_mode {
-      mode = ProgramMode::CharByChar;
-      delay = char_by_char_delay;
+   pub fn seed(mut self, seed: u64) -> Self {
+      self.state.seed = seed;
+      self
    }
 
-   let mut gradient_angle = 0;
+   pub fn easing(mut self, easing: Easing) -> Self {
+      self.state.easing = easing;
+      self
+   }
 
-   if let Some(angle) = arguments.angle {
-      gradient_angle = angle;
+   pub fn duration(mut self, duration: u64) -> Self {
+      self.state.duration = Some(duration);
+      self
    }
 
-   let state: ProgramState = ProgramState::new(gradient_angle as i16, colors, mode, delay.into(), arguments.align_vertically, arguments.align_horizontally);
+   pub fn build(mut self) -> ProgramState {
+      // Without an explicit justification, horizontally aligned messages keep centering each
+      // line on its own, while unaligned ones stay glued to the left edge of the terminal.
+      self.state.justify = match self.justify {
+         Some(justify) => justify,
+         None if self.state.align_horizontally => Justify::Center,
+         None => Justify::Block
+      };
 
-   Ok(state)
-}
\ No newline at end of file
+      self.state
+   }
+}
+
+pub fn split_characters(characters: &str) -> Vec<String> {
+   characters.graphemes(true).map(| grapheme | grapheme.to_string()).collect()
+}
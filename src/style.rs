@@ -0,0 +1,81 @@
+use crate::color::Color;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextAttributes {
+   pub bold: bool,
+   pub dim: bool,
+   pub italic: bool,
+   pub underline: bool,
+   pub blink: bool,
+   pub reverse: bool,
+   pub strikethrough: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attribute {
+   Bold,
+   Dim,
+   Italic,
+   Underline,
+   Blink,
+   Reverse,
+   Strikethrough
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GraphemeStyle {
+   pub foreground: Option<Color>,
+   pub background: Option<Color>,
+   pub attributes: TextAttributes
+}
+
+impl Attribute {
+   pub fn from_name(name: &str) -> Option<Attribute> {
+      match name {
+         "bold" => Some(Attribute::Bold),
+         "dim" => Some(Attribute::Dim),
+         "italic" => Some(Attribute::Italic),
+         "underline" => Some(Attribute::Underline),
+         "blink" => Some(Attribute::Blink),
+         "reverse" => Some(Attribute::Reverse),
+         "strikethrough" => Some(Attribute::Strikethrough),
+         _ => None
+      }
+   }
+}
+
+impl TextAttributes {
+   pub fn set(&mut self, attribute: Attribute, enabled: bool) {
+      match attribute {
+         Attribute::Bold => self.bold = enabled,
+         Attribute::Dim => self.dim = enabled,
+         Attribute::Italic => self.italic = enabled,
+         Attribute::Underline => self.underline = enabled,
+         Attribute::Blink => self.blink = enabled,
+         Attribute::Reverse => self.reverse = enabled,
+         Attribute::Strikethrough => self.strikethrough = enabled
+      }
+   }
+
+   pub fn combine(&self, other: &TextAttributes) -> TextAttributes {
+      TextAttributes {
+         bold: self.bold || other.bold,
+         dim: self.dim || other.dim,
+         italic: self.italic || other.italic,
+         underline: self.underline || other.underline,
+         blink: self.blink || other.blink,
+         reverse: self.reverse || other.reverse,
+         strikethrough: self.strikethrough || other.strikethrough
+      }
+   }
+}
+
+impl GraphemeStyle {
+   pub fn is_empty(&self) -> bool {
+      *self == GraphemeStyle::default()
+   }
+
+   pub fn without_colors(&self) -> GraphemeStyle {
+      GraphemeStyle { foreground: None, background: None, attributes: self.attributes }
+   }
+}
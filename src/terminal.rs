@@ -1,8 +1,13 @@
-use std::io::{stdout, Error};
-use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size, EnterAlternateScreen, LeaveAlternateScreen};
+use std::io::{stdout, Error, Stdout, Write};
+use aligner::{animation::Animation, display::draw_frame, render::RenderCache, state::ProgramState, Frame, ParsedMessage, TerminalSize};
+use clap::ValueEnum;
+use crossterm::cursor::{Hide, Show};
+use crossterm::style::{Attribute, ResetColor, SetAttribute};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size, BeginSynchronizedUpdate, EndSynchronizedUpdate, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::terminal::ClearType::All;
-use crossterm::{cursor::MoveRight, terminal::Clear, ExecutableCommand};
+use crossterm::{terminal::Clear, ExecutableCommand, QueueableCommand};
+
+use crate::recorder::Recorder;
 
 pub enum AlternateScreenAction {
    Enter,
@@ -19,22 +24,100 @@ pub enum CursorVisibilityAction {
    Show
 }
 
-pub struct TerminalSize {
-   pub width: u16,
-   pub height: u16
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum ColorChoice {
+   Auto,
+   Always,
+   Never
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, ValueEnum)]
+pub enum SyncUpdates {
+   Auto,
+   Always,
+   Never
+}
+
+// Collects everything drawn between two flushes and sends it to the terminal in a single
+// write, optionally inside a synchronized update, so partially drawn frames never show up.
+pub struct FrameWriter<'a> {
+   output: Stdout,
+   recorder: Option<&'a mut Recorder>,
+   synchronized: bool,
+   pending: Vec<u8>
+}
+
+impl<'a> FrameWriter<'a> {
+   pub fn new(synchronized: bool, recorder: Option<&'a mut Recorder>) -> FrameWriter<'a> {
+      FrameWriter { output: stdout(), recorder, synchronized, pending: Vec::new() }
+   }
+}
+
+impl Write for FrameWriter<'_> {
+   fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
+      self.pending.extend_from_slice(buffer);
+
+      Ok(buffer.len())
+   }
+
+   fn flush(&mut self) -> Result<(), Error> {
+      if self.pending.is_empty() {
+         return Ok(());
+      }
+
+      if self.synchronized {
+         self.output.queue(BeginSynchronizedUpdate)?;
+      }
+
+      self.output.write_all(&self.pending)?;
+
+      if self.synchronized {
+         self.output.queue(EndSynchronizedUpdate)?;
+      }
+
+      self.output.flush()?;
+
+      if let Some(recorder) = self.recorder.as_mut() {
+         recorder.record_output(&self.pending);
+      }
+
+      self.pending.clear();
+
+      Ok(())
+   }
+}
+
+pub fn print_message(message: &ParsedMessage, state: &ProgramState, dimensions: &TerminalSize, cache: &mut RenderCache, animation: &mut Animation, synchronized: bool, recorder: Option<&mut Recorder>) -> Result<(), Error> {
+   let mut frame = cache.render(message, state, *dimensions);
+   let mut output = FrameWriter::new(synchronized, recorder);
+
+   animation.apply(&mut frame, state, dimensions);
+
+   draw_frame(&frame, dimensions, &mut output)
+}
+
+// Draws an empty frame the way messages are drawn, so a skipped outro still ends on a cleared
+// screen, in the recording too.
+pub fn print_empty_frame(dimensions: &TerminalSize, synchronized: bool, recorder: Option<&mut Recorder>) -> Result<(), Error> {
+   let frame = Frame { width: dimensions.width as usize, height: dimensions.height as usize, rows: Vec::new() };
+
+   draw_frame(&frame, dimensions, &mut FrameWriter::new(synchronized, recorder))
 }
 
-impl TerminalSize {
-   pub fn update(&mut self, width: u16, height: u16) {
-      self.width = width;
-      self.height = height;
+pub fn use_colors(choice: ColorChoice, is_terminal: bool) -> bool {
+   match choice {
+      ColorChoice::Always => true,
+      ColorChoice::Never => false,
+      ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(| value | value.is_empty())
    }
 }
 
-pub fn move_cursor_right(columns: u16) -> Result<(), Error>{
+pub fn reset_display_colors() -> Result<(), Error>{
    let mut stdout = stdout();
 
-   match stdout.execute(MoveRight(columns)) {
+   stdout.queue(SetAttribute(Attribute::Reset))?;
+
+   match stdout.execute(ResetColor) {
       Ok(_) => Ok(()),
       Err(error) => Err(error)
    }
@@ -116,12 +199,107 @@ pub fn set_stdin_raw_mode(action: SetStdinRawModeAction) -> Result<(), Error> {
    }
 }
 
-pub fn move_cursor_to(column: u16, row: u16) -> Result<(), Error> {
-   let mut stdout = stdout();
+pub fn enter_iterative_mode() -> Result<(), String> {
+   alternate_screen(AlternateScreenAction::Enter).map_err(| _ | "Couldn't enter in alternate screen. Exiting.".to_string())?;
 
-   match stdout.execute(MoveTo(column, row)) {
-      Ok(_) => Ok(()),
-      Err(error) => Err(error)
+   set_stdin_raw_mode(SetStdinRawModeAction::Enable).map_err(| _ | {
+      alternate_screen(AlternateScreenAction::Leave).unwrap_or(());
+
+      "Couldn't set the stdin raw mode. Exiting.".to_string()
+   })?;
+
+   change_cursor_visibility(CursorVisibilityAction::Hide).unwrap_or(());
+
+   Ok(())
+}
+
+pub fn leave_iterative_mode() {
+   alternate_screen(AlternateScreenAction::Leave).unwrap_or_else(| _ | {
+      println!("Couldn't leave the alternate screen mode.");
+   });
+
+   set_stdin_raw_mode(SetStdinRawModeAction::Disable).unwrap_or_else(| _ | {
+      print!("Couldn't disable the stdin raw mode.");
+   });
+
+   change_cursor_visibility(CursorVisibilityAction::Show).unwrap_or_else(| _ | {
+      println!("Couldn't change the cursor visibility to visibile.");
+   });
+}
+
+// Asks the terminal for the state of the synchronized update mode (DEC 2026), followed by a
+// primary device attributes request that every terminal answers, so terminals that ignore the
+// first query are detected as soon as the second answer arrives. The stdin raw mode must be on.
+// The answers are read with a bounded poll, and whatever is left unread when the time is up is
+// discarded, so no late answer ends up among the key events.
+#[cfg(unix)]
+pub fn supports_synchronized_updates() -> bool {
+   use std::{fs::OpenOptions, io::{Read, Write}, os::fd::AsRawFd, time::{Duration, Instant}};
+
+   let mut terminal = match OpenOptions::new().read(true).write(true).open("/dev/tty") {
+      Ok(terminal) => terminal,
+      Err(_) => return false
+   };
+
+   if terminal.write_all(b"\x1b[?2026$p\x1b[c").and_then(| _ | terminal.flush()).is_err() {
+      return false;
+   }
+
+   let deadline = Instant::now() + Duration::from_millis(200);
+   let mut response = Vec::new();
+   let mut buffer = [0; 64];
+
+   while !has_device_attributes_report(&response) {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      let mut poll_descriptor = libc::pollfd { fd: terminal.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+
+      // SAFETY: the descriptor points to a single valid pollfd that lives for the whole call.
+      let ready = unsafe { libc::poll(&mut poll_descriptor, 1, remaining.as_millis() as libc::c_int) };
+
+      if ready <= 0 {
+         break;
+      }
+
+      match terminal.read(&mut buffer) {
+         Ok(read) if read > 0 => response.extend_from_slice(&buffer[..read]),
+         _ => break
+      }
    }
+
+   if !has_device_attributes_report(&response) {
+      // SAFETY: the descriptor belongs to the terminal file, which is still open here.
+      unsafe { libc::tcflush(terminal.as_raw_fd(), libc::TCIFLUSH) };
+   }
+
+   parse_synchronized_update_report(&response)
+}
+
+#[cfg(not(unix))]
+pub fn supports_synchronized_updates() -> bool {
+   false
 }
 
+#[cfg(unix)]
+fn has_device_attributes_report(response: &[u8]) -> bool {
+   let prefix = b"\x1b[?";
+
+   let start = match response.windows(prefix.len()).rposition(| window | window == prefix) {
+      Some(index) => index + prefix.len(),
+      None => return false
+   };
+
+   match response[start..].iter().position(| byte | !byte.is_ascii_digit() && *byte != b';') {
+      Some(end) => response[start + end] == b'c',
+      None => false
+   }
+}
+
+#[cfg(unix)]
+fn parse_synchronized_update_report(response: &[u8]) -> bool {
+   let prefix = b"\x1b[?2026;";
+
+   match response.windows(prefix.len()).position(| window | window == prefix) {
+      Some(index) => matches!(response.get(index + prefix.len()), Some(b'1') | Some(b'2') | Some(b'3')),
+      None => false
+   }
+}
@@ -0,0 +1,143 @@
+use crate::{decoration::{for_each_visible_cell, from_canvas, put_grapheme, to_canvas, Cell}, parser::{grapheme_width, Grapheme, ParsedMessage}, state::{Justify, Transform}};
+
+const HORIZONTAL_MIRRORS: [&str; 34] = [
+   "()", "[]", "{}", "<>", "/\\", "«»", "‹›", "┌┐", "└┘", "├┤", "╔╗", "╚╝", "╠╣", "╭╮", "╰╯",
+   "┏┓", "┗┛", "┣┫", "╒╕", "╘╛", "╓╖", "╙╜", "╞╡", "╟╢", "▌▐", "▖▗", "▘▝", "▛▜", "▙▟", "◀▶",
+   "←→", "╱╲", "◢◣", "◥◤"
+];
+
+const VERTICAL_MIRRORS: [&str; 31] = [
+   "/\\", "┌└", "┐┘", "┬┴", "╔╚", "╗╝", "╦╩", "╭╰", "╮╯", "┏┗", "┓┛", "┳┻", "╒╘", "╕╛",
+   "╓╙", "╖╜", "╤╧", "╥╨", "▀▄", "▖▘", "▗▝", "▛▙", "▜▟", "▲▼", "↑↓", "╱╲", "◢◥", "◣◤", "˄˅", "⌒⌣", "⊓⊔"
+];
+
+const CLOCKWISE_ROTATIONS: [&str; 21] = [
+   "─│", "═║", "━┃", "-|", "/\\", "╱╲", "┌┐┘└", "├┬┤┴", "╔╗╝╚", "╠╦╣╩", "╭╮╯╰", "┏┓┛┗",
+   "┣┳┫┻", "▀▐▄▌", "▖▘▝▗", "▛▜▟▙", "↑→↓←", "▲▶▼◀", "<˄>˅", "(⌒)⌣", "[⊓]⊔"
+];
+
+pub fn transform_message(message: &ParsedMessage, transform: Transform, justify: Justify) -> ParsedMessage {
+   let source = to_canvas(message, justify);
+   let width = message.max_line_size;
+   let height = message.lines.len();
+
+   // Rotated wide glyphs still take two columns, so every column of a rotated message holding
+   // any of them gets two cells, keeping the wide glyphs from overlapping their neighbours.
+   let rotated = matches!(transform, Transform::Rotate90 | Transform::Rotate270);
+   let column_scale = if rotated && message.lines.iter().flatten().any(| grapheme | grapheme_width(&grapheme.value) > 1) { 2 } else { 1 };
+
+   let (target_width, target_height) = if rotated { (height * column_scale, width) } else { (width, height) };
+
+   let mut canvas = vec![vec![Cell::Empty; target_width]; target_height];
+
+   for_each_visible_cell(&source, | x, y, grapheme | {
+      let columns = grapheme_width(&grapheme.value).max(1);
+
+      let (target_x, target_y) = match transform {
+         Transform::FlipH => (width - x - columns, y),
+         Transform::FlipV => (x, height - 1 - y),
+         Transform::Rotate90 => ((height - 1 - y) * column_scale, x),
+         Transform::Rotate180 => (width - x - columns, height - 1 - y),
+         Transform::Rotate270 => (y * column_scale, width - 1 - x)
+      };
+
+      let value = remap_grapheme(&grapheme.value, transform);
+
+      put_grapheme(&mut canvas[target_y], target_x, Grapheme { value, style: grapheme.style, glyph: grapheme.glyph });
+   });
+
+   from_canvas(canvas)
+}
+
+fn remap_grapheme(grapheme: &str, transform: Transform) -> String {
+   match transform {
+      Transform::FlipH => mirror(grapheme, &HORIZONTAL_MIRRORS),
+      Transform::FlipV => mirror(grapheme, &VERTICAL_MIRRORS),
+      Transform::Rotate90 => rotate(grapheme, 1),
+      Transform::Rotate180 => mirror(&mirror(grapheme, &HORIZONTAL_MIRRORS), &VERTICAL_MIRRORS),
+      Transform::Rotate270 => rotate(grapheme, -1)
+   }
+}
+
+fn mirror(grapheme: &str, pairs: &[&str]) -> String {
+   cycle_character(grapheme, pairs, 1)
+}
+
+fn rotate(grapheme: &str, steps: isize) -> String {
+   cycle_character(grapheme, &CLOCKWISE_ROTATIONS, steps)
+}
+
+fn cycle_character(grapheme: &str, cycles: &[&str], steps: isize) -> String {
+   let mut characters = grapheme.chars();
+
+   let character = match (characters.next(), characters.next()) {
+      (Some(character), None) => character,
+      _ => return grapheme.to_string()
+   };
+
+   for cycle in cycles {
+      let cycle_characters: Vec<char> = cycle.chars().collect();
+
+      if let Some(index) = cycle_characters.iter().position(| c | *c == character) {
+         let next_index = (index as isize + steps).rem_euclid(cycle_characters.len() as isize) as usize;
+
+         return cycle_characters[next_index].to_string();
+      }
+   }
+
+   grapheme.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{parser::{line_width, parse_message}, state::ProgramState};
+
+   fn parse(message: &str) -> ParsedMessage {
+      parse_message(message.to_string(), &ProgramState::builder().build())
+   }
+
+   fn transform(message: &ParsedMessage, transform: Transform) -> ParsedMessage {
+      transform_message(message, transform, Justify::Left)
+   }
+
+   fn lines(message: &ParsedMessage) -> Vec<String> {
+      message.lines.iter().map(| line | line.iter().map(| grapheme | grapheme.value.as_str()).collect()).collect()
+   }
+
+   #[test]
+   fn flips_corners_horizontally() {
+      let message = transform(&parse("╔═x\n║ab"), Transform::FlipH);
+
+      assert_eq!(lines(&message), ["x═╗", "ba║"]);
+   }
+
+   #[test]
+   fn rotates_back_after_four_quarter_turns() {
+      let message = parse("┌─a\n│b╯");
+      let mut rotated = message.clone();
+
+      for _ in 0..4 {
+         rotated = transform(&rotated, Transform::Rotate90);
+      }
+
+      assert_eq!(lines(&rotated), lines(&message));
+      assert_eq!(rotated.max_line_size, message.max_line_size);
+   }
+
+   #[test]
+   fn rotates_half_a_turn_like_both_flips() {
+      let message = parse("┌─a\n│b╯");
+      let flipped = transform(&transform(&message, Transform::FlipH), Transform::FlipV);
+
+      assert_eq!(lines(&transform(&message, Transform::Rotate180)), lines(&flipped));
+   }
+
+   #[test]
+   fn rotates_wide_glyphs_without_overlapping() {
+      let message = transform(&parse("漢字a"), Transform::Rotate90);
+
+      assert_eq!(lines(&message), ["漢", "  ", "字", "  ", "a "]);
+      assert!(message.lines.iter().all(| line | line_width(line) == message.max_line_size));
+   }
+}
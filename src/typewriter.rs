@@ -1,6 +1,6 @@
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::parser::ParsedMessage;
+use crate::parser::{line_width, Grapheme, ParsedMessage};
 
 const FONT_HEIGHT: usize = 6;
 
@@ -35,8 +35,9 @@ pub fn generate_message(text: String) -> ParsedMessage {
 }
 
 fn create_display_message(mut text: String, graphemes: ExtractedGraphemes) -> ParsedMessage {
-   let mut message: Vec<Vec<String>> = Vec::new();
+   let mut message: Vec<Vec<Grapheme>> = Vec::new();
    let mut max_line_size = 0;
+   let mut glyph_offset = 0;
 
    text = text.to_uppercase();
    
@@ -47,25 +48,25 @@ fn create_display_message(mut text: String, graphemes: ExtractedGraphemes) -> Pa
       let line_characters: Vec<char> = line.chars().collect();
 
       for i in 0..FONT_HEIGHT {
-         let mut line: Vec<String> = Vec::new();
+         let mut line: Vec<Grapheme> = Vec::new();
    
          for j in 0..line_characters.len() {
             let c = line_characters[j];
    
             if let Some(grapheme_lines) = get_grapheme_lines(c, &graphemes) {
-               line.extend(grapheme_lines[i as usize].clone());
+               line.extend(grapheme_lines[i as usize].iter().map(| value | Grapheme { value: value.clone(), style: None, glyph: Some(glyph_offset + j) }));
             }
          }
    
-         if line.len() > max_line_size {
-            max_line_size = line.len();
-         }
+         max_line_size = max_line_size.max(line_width(&line));
    
          message.push(line);
       }
 
+      glyph_offset += line_characters.len();
+
       if split_text.len() > 1 {
-         message.push(Vec::from([" ".to_string()]));
+         message.push(Vec::from([Grapheme::blank()]));
       }
    }
 
@@ -73,11 +74,11 @@ fn create_display_message(mut text: String, graphemes: ExtractedGraphemes) -> Pa
 }
 
 fn get_grapheme_lines(c: char, graphemes: &ExtractedGraphemes) -> Option<Vec<Vec<String>>> {
-   if c.is_numeric() {
+   if c.is_ascii_digit() {
       let index = c as usize - 48;
 
       Some(graphemes.numbers[index].clone())
-   } else if c.is_alphabetic() {
+   } else if c.is_ascii_alphabetic() {
       let index = c as usize - 65;
 
       Some(graphemes.characters[index].clone())
@@ -179,3 +180,14 @@ fn extract_punctuation(font_slice: Vec<&str>, target: &mut Vec<Vec<String>>) {
 
    *target = lines;
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn skips_letters_and_digits_outside_ascii() {
+      assert_eq!(generate_message("café١".to_string()).max_line_size, generate_message("caf".to_string()).max_line_size);
+      assert_eq!(generate_message("こんにちは".to_string()).max_line_size, 0);
+   }
+}